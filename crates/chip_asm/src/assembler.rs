@@ -0,0 +1,741 @@
+use std::collections::{HashMap, VecDeque};
+
+use chip_core::{disasm::Instruction, globals::XO_RAM_SIZE};
+
+use crate::{
+    calc,
+    errors::AsmError,
+    lexer::{Token, tokenize, parse_number}
+};
+
+/// Programs are loaded here, like in Octo
+pub const START: usize = 0x200;
+// guards against recursive macros
+const MAX_EXPANSIONS: usize = 1 << 16;
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>
+}
+
+enum Patch {
+    /// 12-bit address of an instruction
+    Addr,
+    /// 16-bit word following `i := long`
+    Long
+}
+
+struct Fixup {
+    at: usize,
+    label: Token,
+    patch: Patch
+}
+
+enum Flow {
+    /// `if .. begin`, the jump skips to `else` or `end`
+    Begin { jump: usize, token: Token },
+    /// the jump skips the else branch
+    Else { jump: usize, token: Token },
+    Loop { start: usize, whiles: Vec<usize>, token: Token }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CondOp {
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Key,
+    NotKey
+}
+impl CondOp {
+    fn negate(self) -> CondOp {
+        match self {
+            CondOp::Eq => CondOp::Ne,
+            CondOp::Ne => CondOp::Eq,
+            CondOp::Lt => CondOp::Ge,
+            CondOp::Ge => CondOp::Lt,
+            CondOp::Gt => CondOp::Le,
+            CondOp::Le => CondOp::Gt,
+            CondOp::Key => CondOp::NotKey,
+            CondOp::NotKey => CondOp::Key,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operand {
+    Reg(u8),
+    Imm(u8)
+}
+
+/// Turns Octo source into a binary to be loaded at 0x200
+pub fn assemble(src: &str) -> Result<Vec<u8>, AsmError> {
+    // 0x200 holds a jump to main, unless main comes first anyway
+    let mut asm = Assembler::new(src, true);
+    asm.run()?;
+    match asm.labels.get("main").copied() {
+        Some(main) if main as usize != START + 2 => {
+            let [hi, lo] = Instruction::Jump(main).encode().to_be_bytes();
+            asm.memory[START] = hi;
+            asm.memory[START + 1] = lo;
+        },
+        _ => {
+            asm = Assembler::new(src, false);
+            asm.run()?;
+        }
+    }
+    Ok(asm.memory[START..asm.end.max(START)].to_vec())
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    last: Token,
+    memory: Vec<u8>,
+    pos: usize,
+    end: usize,
+    labels: HashMap<String, u16>,
+    consts: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    flow: Vec<Flow>,
+    expansions: usize
+}
+impl Assembler {
+    fn new(src: &str, jump_to_main: bool) -> Self {
+        let tokens: VecDeque<Token> = tokenize(src).into();
+        let last = tokens.back().cloned().unwrap_or(Token { text: String::new(), line: 1, col: 1 });
+        let mut asm = Assembler {
+            tokens,
+            last,
+            memory: vec![0; XO_RAM_SIZE],
+            pos: START,
+            end: START,
+            labels: HashMap::new(),
+            consts: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            flow: Vec::new(),
+            expansions: 0
+        };
+        if jump_to_main {
+            asm.pos += 2;
+            asm.end = asm.pos;
+        }
+        asm
+    }
+    fn run(&mut self) -> Result<(), AsmError> {
+        while let Some(token) = self.tokens.pop_front() {
+            self.statement(token)?;
+        }
+        match self.flow.pop() {
+            Some(Flow::Begin { token, .. }) | Some(Flow::Else { token, .. }) => {
+                return Err(token.error("'begin' without 'end'"));
+            },
+            Some(Flow::Loop { token, .. }) => return Err(token.error("'loop' without 'again'")),
+            None => ()
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(addr) = self.labels.get(&fixup.label.text).copied() else {
+                return Err(fixup.label.error(format!("undefined label '{}'", fixup.label.text)));
+            };
+            match fixup.patch {
+                Patch::Addr => {
+                    if addr > 0xFFF {
+                        return Err(fixup.label.error(format!("'{}' is out of the 12-bit range", fixup.label.text)));
+                    }
+                    self.memory[fixup.at] |= (addr >> 8) as u8;
+                    self.memory[fixup.at + 1] = addr as u8;
+                },
+                Patch::Long => self.memory[fixup.at..fixup.at + 2].copy_from_slice(&addr.to_be_bytes())
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        self.tokens.pop_front().ok_or_else(|| self.last.error("unexpected end of file"))
+    }
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(token.error(format!("expected '{}', found '{}'", text, token.text)));
+        }
+        Ok(token)
+    }
+    fn peek_is(&self, text: &str) -> bool {
+        self.tokens.front().is_some_and(|t| t.text == text)
+    }
+
+    fn emit(&mut self, byte: u8, token: &Token) -> Result<(), AsmError> {
+        if self.pos >= self.memory.len() { return Err(token.error("program does not fit in memory")) }
+        self.memory[self.pos] = byte;
+        self.pos += 1;
+        self.end = self.end.max(self.pos);
+        Ok(())
+    }
+    fn inst(&mut self, inst: Instruction, token: &Token) -> Result<(), AsmError> {
+        let [hi, lo] = inst.encode().to_be_bytes();
+        self.emit(hi, token)?;
+        self.emit(lo, token)
+    }
+    /// Emits a jump to be patched later, returns its location
+    fn placeholder(&mut self, token: &Token) -> Result<usize, AsmError> {
+        let at = self.pos;
+        self.inst(Instruction::Jump(0), token)?;
+        Ok(at)
+    }
+    fn patch_jump(&mut self, at: usize, token: &Token) -> Result<(), AsmError> {
+        if self.pos > 0xFFF { return Err(token.error("jump target is out of the 12-bit range")) }
+        let [hi, lo] = Instruction::Jump(self.pos as u16).encode().to_be_bytes();
+        self.memory[at] = hi;
+        self.memory[at + 1] = lo;
+        Ok(())
+    }
+
+    fn reg_of(&self, token: &Token) -> Option<u8> {
+        if let Some(reg) = self.aliases.get(&token.text) { return Some(*reg) }
+        let hex = token.text.strip_prefix('v').or_else(|| token.text.strip_prefix('V'))?;
+        if hex.len() != 1 { return None }
+        u8::from_str_radix(hex, 16).ok()
+    }
+    fn reg(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+        self.reg_of(&token).ok_or_else(|| token.error(format!("expected a register, found '{}'", token.text)))
+    }
+    fn number_of(&self, token: &Token) -> Option<f64> {
+        parse_number(&token.text).or_else(|| self.consts.get(&token.text).copied())
+    }
+    fn number(&mut self) -> Result<f64, AsmError> {
+        let token = self.next()?;
+        self.number_of(&token).ok_or_else(|| token.error(format!("expected a number, found '{}'", token.text)))
+    }
+    fn byte_of(&self, token: &Token) -> Result<u8, AsmError> {
+        let val = self.number_of(token)
+            .ok_or_else(|| token.error(format!("expected a number, found '{}'", token.text)))?;
+        if !(-128. ..256.).contains(&val) {
+            return Err(token.error(format!("{} does not fit in a byte", val)));
+        }
+        Ok(val as i64 as u8)
+    }
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+        self.byte_of(&token)
+    }
+    fn nibble(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+        let val = self.byte_of(&token)?;
+        if val > 0xF { return Err(token.error(format!("{} does not fit in 4 bits", val))) }
+        Ok(val)
+    }
+    fn operand(&mut self) -> Result<Operand, AsmError> {
+        let token = self.next()?;
+        if let Some(reg) = self.reg_of(&token) { return Ok(Operand::Reg(reg)) }
+        Ok(Operand::Imm(self.byte_of(&token)?))
+    }
+    /// Reads an address, labels defined later are patched in by `run`
+    fn address(&mut self, at: usize, patch: Patch) -> Result<u16, AsmError> {
+        let token = self.next()?;
+        let max = match patch {
+            Patch::Addr => 0xFFF,
+            Patch::Long => 0xFFFF
+        };
+        let val = if let Some(val) = self.number_of(&token) {
+            val
+        } else if let Some(addr) = self.labels.get(&token.text) {
+            *addr as f64
+        } else {
+            if self.reg_of(&token).is_some() || token.text.starts_with(':') {
+                return Err(token.error(format!("expected an address, found '{}'", token.text)));
+            }
+            self.fixups.push(Fixup { at, label: token, patch });
+            return Ok(0);
+        };
+        if !(0. ..=max as f64).contains(&val) {
+            return Err(token.error(format!("address {} is out of range", val)));
+        }
+        Ok(val as u16)
+    }
+    /// Reads the name being defined by a directive
+    fn name(&mut self) -> Result<Token, AsmError> {
+        let token = self.next()?;
+        if parse_number(&token.text).is_some() || self.reg_of(&token).is_some() {
+            return Err(token.error(format!("'{}' cannot be used as a name", token.text)));
+        }
+        Ok(token)
+    }
+    /// Tokens between a pair of braces, nested braces included
+    fn block(&mut self) -> Result<(Vec<Token>, Token), AsmError> {
+        self.expect("{")?;
+        let mut depth = 0;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next()?;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" if depth == 0 => return Ok((body, token)),
+                "}" => depth -= 1,
+                _ => ()
+            }
+            body.push(token);
+        }
+    }
+    fn calc(&mut self) -> Result<f64, AsmError> {
+        let (tokens, end) = self.block()?;
+        let here = self.pos as f64;
+        calc::eval(&tokens, &end, &|name| {
+            if name == "HERE" { return Some(here) }
+            self.consts.get(name).copied().or_else(|| self.labels.get(name).map(|a| *a as f64))
+        })
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), AsmError> {
+        if let Some(x) = self.reg_of(&token) {
+            return self.reg_statement(x, token);
+        }
+        if let Some(mac) = self.macros.get(&token.text) {
+            return self.expand(token, mac.args.len());
+        }
+        let t = &token;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.insert(name.text.clone(), self.pos as u16).is_some() {
+                    return Err(name.error(format!("label '{}' is already defined", name.text)));
+                }
+            },
+            ":const" => {
+                let name = self.name()?;
+                let val = self.number()?;
+                self.consts.insert(name.text, val);
+            },
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.reg()?;
+                self.aliases.insert(name.text, reg);
+            },
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+                while !self.peek_is("{") {
+                    args.push(self.name()?.text);
+                }
+                let (body, _) = self.block()?;
+                self.macros.insert(name.text, Macro { args, body });
+            },
+            ":calc" => {
+                let name = self.name()?;
+                let val = self.calc()?;
+                self.consts.insert(name.text, val);
+            },
+            ":org" => {
+                let addr = self.next()?;
+                let val = self.number_of(&addr).ok_or_else(|| addr.error("expected an address"))?;
+                if !(START as f64..XO_RAM_SIZE as f64).contains(&val) {
+                    return Err(addr.error(format!("cannot place code at {}", val)));
+                }
+                self.pos = val as usize;
+            },
+            ":byte" => {
+                let val = if self.peek_is("{") {
+                    let val = self.calc()?;
+                    if !(-128. ..256.).contains(&val) { return Err(t.error(format!("{} does not fit in a byte", val))) }
+                    val as i64 as u8
+                } else {
+                    self.byte()?
+                };
+                self.emit(val, t)?;
+            },
+            ":call" => {
+                let addr = self.address(self.pos, Patch::Addr)?;
+                self.inst(Instruction::Call(addr), t)?;
+            },
+            ":breakpoint" => {
+                self.name()?;
+            },
+            "clear" => self.inst(Instruction::Cls, t)?,
+            "return" => self.inst(Instruction::Ret, t)?,
+            "exit" => self.inst(Instruction::Exit, t)?,
+            "lores" => self.inst(Instruction::Lores, t)?,
+            "hires" => self.inst(Instruction::Hires, t)?,
+            "scroll-left" => self.inst(Instruction::ScrollLeft, t)?,
+            "scroll-right" => self.inst(Instruction::ScrollRight, t)?,
+            "audio" => self.inst(Instruction::Audio, t)?,
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.inst(Instruction::ScrollDown(n), t)?;
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.inst(Instruction::ScrollUp(n), t)?;
+            },
+            "plane" => {
+                let n = self.nibble()?;
+                self.inst(Instruction::Planes(n), t)?;
+            },
+            "jump" => {
+                let addr = self.address(self.pos, Patch::Addr)?;
+                self.inst(Instruction::Jump(addr), t)?;
+            },
+            "jump0" => {
+                let addr = self.address(self.pos, Patch::Addr)?;
+                self.inst(Instruction::JumpOffset(addr), t)?;
+            },
+            "sprite" => {
+                let (x, y) = (self.reg()?, self.reg()?);
+                let n = self.nibble()?;
+                self.inst(Instruction::Draw(x, y, n), t)?;
+            },
+            "save" | "load" => {
+                let x = self.reg()?;
+                let inst = if self.peek_is("-") {
+                    self.next()?;
+                    let y = self.reg()?;
+                    if token.text == "save" { Instruction::SaveRange(x, y) } else { Instruction::LoadRange(x, y) }
+                } else if token.text == "save" {
+                    Instruction::Store(x)
+                } else {
+                    Instruction::Load(x)
+                };
+                self.inst(inst, t)?;
+            },
+            "bcd" => {
+                let x = self.reg()?;
+                self.inst(Instruction::Bcd(x), t)?;
+            },
+            "saveflags" => {
+                let x = self.reg()?;
+                self.inst(Instruction::SaveFlags(x), t)?;
+            },
+            "loadflags" => {
+                let x = self.reg()?;
+                self.inst(Instruction::LoadFlags(x), t)?;
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.reg()?;
+                let inst = match token.text.as_str() {
+                    "delay" => Instruction::SetDelay(x),
+                    "buzzer" => Instruction::SetSound(x),
+                    _ => Instruction::Pitch(x)
+                };
+                self.inst(inst, t)?;
+            },
+            "i" => self.i_statement(t)?,
+            "if" => {
+                let cond = self.condition()?;
+                let next = self.next()?;
+                match next.text.as_str() {
+                    "then" => self.skip_unless(cond, t)?,
+                    "begin" => {
+                        self.skip_unless((cond.0, cond.1.negate(), cond.2), t)?;
+                        let jump = self.placeholder(t)?;
+                        self.flow.push(Flow::Begin { jump, token: token.clone() });
+                    },
+                    _ => return Err(next.error(format!("expected 'then' or 'begin', found '{}'", next.text)))
+                }
+            },
+            "else" => {
+                let Some(Flow::Begin { jump, .. }) = self.flow.pop() else {
+                    return Err(t.error("'else' without 'begin'"));
+                };
+                let skip = self.placeholder(t)?;
+                self.patch_jump(jump, t)?;
+                self.flow.push(Flow::Else { jump: skip, token: token.clone() });
+            },
+            "end" => match self.flow.pop() {
+                Some(Flow::Begin { jump, .. }) | Some(Flow::Else { jump, .. }) => self.patch_jump(jump, t)?,
+                _ => return Err(t.error("'end' without 'begin'"))
+            },
+            "loop" => self.flow.push(Flow::Loop { start: self.pos, whiles: Vec::new(), token: token.clone() }),
+            "while" => {
+                if !matches!(self.flow.last(), Some(Flow::Loop { .. })) {
+                    return Err(t.error("'while' outside of a loop"));
+                }
+                let cond = self.condition()?;
+                self.skip_unless((cond.0, cond.1.negate(), cond.2), t)?;
+                let jump = self.placeholder(t)?;
+                if let Some(Flow::Loop { whiles, .. }) = self.flow.last_mut() {
+                    whiles.push(jump);
+                }
+            },
+            "again" => {
+                let Some(Flow::Loop { start, whiles, .. }) = self.flow.pop() else {
+                    return Err(t.error("'again' without 'loop'"));
+                };
+                self.inst(Instruction::Jump(start as u16), t)?;
+                for jump in whiles {
+                    self.patch_jump(jump, t)?;
+                }
+            },
+            text if text.starts_with(':') => {
+                return Err(t.error(format!("unknown directive '{}'", text)));
+            },
+            _ => {
+                if self.number_of(t).is_some() {
+                    // bare numbers are data
+                    let val = self.byte_of(t)?;
+                    self.emit(val, t)?;
+                } else {
+                    // a bare label name calls the subroutine
+                    self.tokens.push_front(token.clone());
+                    let addr = self.address(self.pos, Patch::Addr)?;
+                    self.inst(Instruction::Call(addr), t)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn reg_statement(&mut self, x: u8, token: Token) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let t = &token;
+        let inst = match op.text.as_str() {
+            ":=" => {
+                let rhs = self.next()?;
+                match rhs.text.as_str() {
+                    "random" => Instruction::Random(x, self.byte()?),
+                    "delay" => Instruction::GetDelay(x),
+                    "key" => Instruction::WaitKey(x),
+                    _ => match self.reg_of(&rhs) {
+                        Some(y) => Instruction::Set(x, y),
+                        None => Instruction::SetImm(x, self.byte_of(&rhs)?)
+                    }
+                }
+            },
+            "+=" => match self.operand()? {
+                Operand::Reg(y) => Instruction::Add(x, y),
+                Operand::Imm(n) => Instruction::AddImm(x, n)
+            },
+            "-=" => match self.operand()? {
+                Operand::Reg(y) => Instruction::Sub(x, y),
+                Operand::Imm(n) => Instruction::AddImm(x, n.wrapping_neg())
+            },
+            "=-" => Instruction::SubN(x, self.reg()?),
+            "|=" => Instruction::Or(x, self.reg()?),
+            "&=" => Instruction::And(x, self.reg()?),
+            "^=" => Instruction::Xor(x, self.reg()?),
+            ">>=" => Instruction::Shr(x, self.reg()?),
+            "<<=" => Instruction::Shl(x, self.reg()?),
+            _ => return Err(op.error(format!("unknown operator '{}'", op.text)))
+        };
+        self.inst(inst, t)
+    }
+    fn i_statement(&mut self, t: &Token) -> Result<(), AsmError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => {
+                if self.peek_is("hex") || self.peek_is("bighex") {
+                    let font = self.next()?;
+                    let x = self.reg()?;
+                    let inst = if font.text == "hex" { Instruction::Font(x) } else { Instruction::BigFont(x) };
+                    self.inst(inst, t)
+                } else if self.peek_is("long") {
+                    self.next()?;
+                    self.inst(Instruction::LongI, t)?;
+                    let addr = self.address(self.pos, Patch::Long)?;
+                    let [hi, lo] = addr.to_be_bytes();
+                    self.emit(hi, t)?;
+                    self.emit(lo, t)
+                } else {
+                    let addr = self.address(self.pos, Patch::Addr)?;
+                    self.inst(Instruction::SetI(addr), t)
+                }
+            },
+            "+=" => {
+                let x = self.reg()?;
+                self.inst(Instruction::AddI(x), t)
+            },
+            _ => Err(op.error(format!("unknown operator '{}'", op.text)))
+        }
+    }
+    fn condition(&mut self) -> Result<(u8, CondOp, Operand), AsmError> {
+        let x = self.reg()?;
+        let op = self.next()?;
+        let op = match op.text.as_str() {
+            "key" => return Ok((x, CondOp::Key, Operand::Imm(0))),
+            "-key" => return Ok((x, CondOp::NotKey, Operand::Imm(0))),
+            "==" => CondOp::Eq,
+            "!=" => CondOp::Ne,
+            "<" => CondOp::Lt,
+            ">" => CondOp::Gt,
+            "<=" => CondOp::Le,
+            ">=" => CondOp::Ge,
+            _ => return Err(op.error(format!("unknown comparison '{}'", op.text)))
+        };
+        Ok((x, op, self.operand()?))
+    }
+    /// Emits code that skips the following instruction when the condition is false
+    fn skip_unless(&mut self, (x, op, rhs): (u8, CondOp, Operand), t: &Token) -> Result<(), AsmError> {
+        let inst = match (op, rhs) {
+            (CondOp::Eq, Operand::Imm(n)) => Instruction::SkipNeImm(x, n),
+            (CondOp::Eq, Operand::Reg(y)) => Instruction::SkipNe(x, y),
+            (CondOp::Ne, Operand::Imm(n)) => Instruction::SkipEqImm(x, n),
+            (CondOp::Ne, Operand::Reg(y)) => Instruction::SkipEq(x, y),
+            (CondOp::Key, _) => Instruction::SkipNotKey(x),
+            (CondOp::NotKey, _) => Instruction::SkipKey(x),
+            _ => {
+                // VF gets the borrow flag of a subtraction, set when there was no borrow
+                let x_minus_y = matches!(op, CondOp::Lt | CondOp::Ge);
+                match rhs {
+                    Operand::Reg(y) => {
+                        self.inst(Instruction::Set(0xF, x), t)?;
+                        self.inst(if x_minus_y { Instruction::Sub(0xF, y) } else { Instruction::SubN(0xF, y) }, t)?;
+                    },
+                    Operand::Imm(n) => {
+                        self.inst(Instruction::SetImm(0xF, n), t)?;
+                        self.inst(if x_minus_y { Instruction::SubN(0xF, x) } else { Instruction::Sub(0xF, x) }, t)?;
+                    }
+                }
+                // < and > hold when there was a borrow
+                if matches!(op, CondOp::Lt | CondOp::Gt) {
+                    Instruction::SkipNeImm(0xF, 0)
+                } else {
+                    Instruction::SkipEqImm(0xF, 0)
+                }
+            }
+        };
+        self.inst(inst, t)
+    }
+    fn expand(&mut self, token: Token, arg_count: usize) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(token.error(format!("too many expansions of '{}'", token.text)));
+        }
+        let mut values = Vec::with_capacity(arg_count);
+        for _ in 0..arg_count {
+            values.push(self.next()?);
+        }
+        let mac = &self.macros[&token.text];
+        let expanded: Vec<Token> = mac.body.iter().map(|t| {
+            match mac.args.iter().position(|a| *a == t.text) {
+                Some(i) => Token { text: values[i].text.clone(), ..t.clone() },
+                None => t.clone()
+            }
+        }).collect();
+        for t in expanded.into_iter().rev() {
+            self.tokens.push_front(t);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(src: &str) -> Vec<u16> {
+        assemble(src).unwrap().chunks(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect()
+    }
+
+    #[test]
+    fn basic_instructions() {
+        let rom = words("
+            : main
+                clear
+                v0 := 0x12  v1 += 3  v2 := v1  v3 -= 1
+                i := hex v0  sprite v0 v1 5
+                save v3  load v1 - v4
+            ");
+        assert!(rom == [0x00E0, 0x6012, 0x7103, 0x8210, 0x73FF, 0xF029, 0xD015, 0xF355, 0x5143]);
+    }
+    #[test]
+    fn labels_and_jump_to_main() {
+        let rom = words("
+            : data 0x3C 0x42
+            : main
+                i := data
+                jump main
+            : sub return
+            ");
+        assert!(rom == [0x1204, 0x3C42, 0xA202, 0x1204, 0x00EE]);
+        // main first needs no jump, calls go to bare labels
+        let rom = words(": main sub exit : sub return");
+        assert!(rom == [0x2204, 0x00FD, 0x00EE]);
+    }
+    #[test]
+    fn const_alias_calc() {
+        let rom = assemble("
+            :const SPEED 3
+            :alias speed v5
+            :calc DOUBLE { SPEED * 2 }
+            : main
+                speed := DOUBLE
+                speed += SPEED
+                :byte { DOUBLE + 1 }
+            ").unwrap();
+        assert!(rom == [0x65, 0x06, 0x75, 0x03, 0x07]);
+        assert!(assemble(":calc X { 1 } : main :byte { X + 1 }").unwrap() == [2]);
+    }
+    #[test]
+    fn macros() {
+        let rom = words("
+            :macro swap A B { vf := A A := B B := vf }
+            : main swap v1 v2
+            ");
+        assert!(rom == [0x8F10, 0x8120, 0x82F0]);
+    }
+    #[test]
+    fn org() {
+        let rom = assemble(": main jump 0x300 :org 0x300 : next jump next").unwrap();
+        assert!(rom.len() == 0x102);
+        assert!(rom[0x100..] == [0x13, 0x00]);
+    }
+    #[test]
+    fn structured_if() {
+        let rom = words("
+            : main
+                if v0 == 1 then v1 := 2
+                if v0 != v1 begin
+                    v2 := 3
+                else
+                    v2 := 4
+                end
+                if v3 key then clear
+            ");
+        assert!(rom == [
+            0x4001, 0x6102,
+            0x9010, 0x120C,
+            0x6203, 0x120E,
+            0x6204,
+            0xE3A1, 0x00E0
+        ]);
+    }
+    #[test]
+    fn comparisons() {
+        let rom = words(": main if v1 < v2 then v3 := 1");
+        assert!(rom == [0x8F10, 0x8F25, 0x4F00, 0x6301]);
+        let rom = words(": main if v1 >= 5 then v3 := 1");
+        assert!(rom == [0x6F05, 0x8F17, 0x3F00, 0x6301]);
+    }
+    #[test]
+    fn loops() {
+        let rom = words("
+            : main
+                loop
+                    v0 += 1
+                    while v0 != 10
+                again
+            ");
+        assert!(rom == [0x7001, 0x400A, 0x1208, 0x1200]);
+    }
+    #[test]
+    fn long_i() {
+        let rom = assemble(": main i := long data :org 0x1000 : data 0xAA").unwrap();
+        assert!(rom[..4] == [0xF0, 0x00, 0x10, 0x00]);
+        assert!(rom[0x1000 - START] == 0xAA);
+    }
+    #[test]
+    fn errors() {
+        assert!(assemble(": main\n  v0 := 300") == Err(AsmError::new(2, 9, "300 does not fit in a byte")));
+        assert!(assemble(": main jump nowhere") == Err(AsmError::new(1, 13, "undefined label 'nowhere'")));
+        assert!(assemble(": main\nloop v0 += 1") == Err(AsmError::new(2, 1, "'loop' without 'again'")));
+        assert!(assemble(": main v0 :=") == Err(AsmError::new(1, 11, "unexpected end of file")));
+        assert!(assemble(": main : main") == Err(AsmError::new(1, 10, "label 'main' is already defined")));
+        assert!(assemble(": main :foo") == Err(AsmError::new(1, 8, "unknown directive ':foo'")));
+        assert!(assemble(": main end") == Err(AsmError::new(1, 8, "'end' without 'begin'")));
+    }
+}
@@ -0,0 +1,112 @@
+//! `:calc` expressions.
+//!
+//! Like in Octo there is no operator precedence, binary operators are
+//! evaluated right to left: `2 * 3 + 1` is `2 * (3 + 1)`.
+use crate::{errors::AsmError, lexer::{Token, parse_number}};
+
+/// Evaluates the tokens between the braces, `end` is the closing brace
+pub fn eval(tokens: &[Token], end: &Token, lookup: &dyn Fn(&str) -> Option<f64>) -> Result<f64, AsmError> {
+    let mut parser = Parser { tokens, pos: 0, end, lookup };
+    let val = parser.expr()?;
+    if let Some(token) = tokens.get(parser.pos) {
+        return Err(token.error(format!("unexpected '{}' in expression", token.text)));
+    }
+    Ok(val)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    end: &'a Token,
+    lookup: &'a dyn Fn(&str) -> Option<f64>
+}
+impl Parser<'_> {
+    fn next(&mut self) -> Result<&Token, AsmError> {
+        let token = self.tokens.get(self.pos).ok_or_else(|| self.end.error("incomplete expression"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+    fn expr(&mut self) -> Result<f64, AsmError> {
+        let lhs = self.term()?;
+        let Some(op) = self.tokens.get(self.pos) else { return Ok(lhs) };
+        if op.text == ")" { return Ok(lhs) }
+        let op = op.clone();
+        self.pos += 1;
+        let rhs = self.expr()?;
+        let int = |v: f64| v as i64;
+        let shift = |f: fn(i64, u32) -> Option<i64>| {
+            u32::try_from(int(rhs)).ok().and_then(|n| f(int(lhs), n)).ok_or_else(|| op.error("shift out of range"))
+        };
+        Ok(match op.text.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" | "%" if rhs == 0. => return Err(op.error("division by zero")),
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (int(lhs) & int(rhs)) as f64,
+            "|" => (int(lhs) | int(rhs)) as f64,
+            "^" => (int(lhs) ^ int(rhs)) as f64,
+            "<<" => shift(i64::checked_shl)? as f64,
+            ">>" => shift(i64::checked_shr)? as f64,
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            _ => return Err(op.error(format!("unknown operator '{}'", op.text)))
+        })
+    }
+    fn term(&mut self) -> Result<f64, AsmError> {
+        let token = self.next()?.clone();
+        match token.text.as_str() {
+            "(" => {
+                let val = self.expr()?;
+                let close = self.next()?;
+                if close.text != ")" { return Err(close.error("expected ')'")) }
+                Ok(val)
+            },
+            "-" => Ok(-self.term()?),
+            "~" => Ok(!(self.term()? as i64) as f64),
+            "!" => Ok(if self.term()? == 0. { 1. } else { 0. }),
+            "floor" => Ok(self.term()?.floor()),
+            "sqrt" => Ok(self.term()?.sqrt()),
+            "sin" => Ok(self.term()?.sin()),
+            "cos" => Ok(self.term()?.cos()),
+            text => parse_number(text)
+                .or_else(|| (self.lookup)(text))
+                .ok_or_else(|| token.error(format!("undefined name '{}'", text)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+
+    fn calc(src: &str) -> Result<f64, AsmError> {
+        let tokens = tokenize(src);
+        let end = Token { text: "}".into(), line: 1, col: src.len() + 1 };
+        eval(&tokens, &end, &|name| if name == "SIZE" { Some(8.) } else { None })
+    }
+
+    #[test]
+    fn right_to_left() {
+        assert!(calc("2 * 3 + 1") == Ok(8.));
+        assert!(calc("( 2 * 3 ) + 1") == Ok(7.));
+        assert!(calc("10 - 2 - 1") == Ok(9.));
+    }
+    #[test]
+    fn names_and_operators() {
+        assert!(calc("SIZE << 1") == Ok(16.));
+        assert!(calc("0xF0 & - 1") == Ok(240.));
+        assert!(calc("floor ( 7 / 2 )") == Ok(3.));
+        assert!(calc("3 max SIZE") == Ok(8.));
+    }
+    #[test]
+    fn errors() {
+        assert!(calc("1 + FOO") == Err(AsmError::new(1, 5, "undefined name 'FOO'")));
+        assert!(calc("1 +") == Err(AsmError::new(1, 4, "incomplete expression")));
+        assert!(calc("1 / 0") == Err(AsmError::new(1, 3, "division by zero")));
+        assert!(calc("1 << 64") == Err(AsmError::new(1, 3, "shift out of range")));
+        assert!(calc("1 >> - 1") == Err(AsmError::new(1, 3, "shift out of range")));
+    }
+}
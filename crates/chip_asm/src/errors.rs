@@ -0,0 +1,20 @@
+use std::fmt;
+
+/// Assembly failure at a 1-based source position
+#[derive(Clone, Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub col: usize,
+    pub message: String
+}
+impl AsmError {
+    pub fn new(line: usize, col: usize, message: impl Into<String>) -> Self {
+        AsmError { line, col, message: message.into() }
+    }
+}
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.message)
+    }
+}
+impl std::error::Error for AsmError {}
@@ -0,0 +1,74 @@
+use crate::errors::AsmError;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,
+    pub col: usize
+}
+impl Token {
+    pub fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError::new(self.line, self.col, message)
+    }
+}
+
+/// Splits the source on whitespace, `#` comments run to the end of the line
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line_idx, line) in src.lines().enumerate() {
+        let mut current: Option<Token> = None;
+        for (col_idx, c) in line.chars().enumerate() {
+            if c.is_whitespace() {
+                tokens.extend(current.take());
+                continue;
+            }
+            if c == '#' && current.is_none() { break }
+            current.get_or_insert_with(|| Token {
+                text: String::new(),
+                line: line_idx + 1,
+                col: col_idx + 1
+            }).text.push(c);
+        }
+        tokens.extend(current);
+    }
+    tokens
+}
+
+/// Parses decimal, 0x hexadecimal and 0b binary literals with an optional sign
+pub fn parse_number(text: &str) -> Option<f64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text)
+    };
+    let val = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse::<f64>().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -val } else { val })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn positions_and_comments() {
+        let tokens = tokenize("v0 := 1 # comment\n  : main");
+        let texts: Vec<_> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert!(texts == ["v0", ":=", "1", ":", "main"]);
+        assert!((tokens[3].line, tokens[3].col) == (2, 3));
+        assert!((tokens[4].line, tokens[4].col) == (2, 5));
+    }
+    #[test]
+    fn numbers() {
+        assert!(parse_number("0x1F") == Some(31.));
+        assert!(parse_number("0b101") == Some(5.));
+        assert!(parse_number("-12") == Some(-12.));
+        assert!(parse_number("v0").is_none());
+        assert!(parse_number("0xZZ").is_none());
+    }
+}
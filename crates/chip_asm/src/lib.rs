@@ -0,0 +1,9 @@
+//! Assembler for Octo, the CHIP-8 high level assembly language.
+//! The output is meant to be loaded at 0x200 with `Cpu::load_rom`.
+mod assembler;
+mod calc;
+mod errors;
+mod lexer;
+
+pub use assembler::{assemble, START};
+pub use errors::AsmError;
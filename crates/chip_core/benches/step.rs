@@ -0,0 +1,40 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use chip_core::Cpu;
+
+// arithmetic, BCD and sprite drawing in an endless loop
+const ROM: [u8; 24] = [
+    0x70, 0x01,
+    0x81, 0x04,
+    0x82, 0x15,
+    0x83, 0x26,
+    0xa3, 0x00,
+    0xf0, 0x33,
+    0xf2, 0x65,
+    0xf0, 0x29,
+    0xd1, 0x25,
+    0x34, 0x00,
+    0x74, 0x01,
+    0x12, 0x00
+];
+const STEPS: usize = 10_000;
+
+fn run(cache: bool) -> u8 {
+    let mut cpu = Cpu::new();
+    cpu.set_decode_cache(cache);
+    cpu.load_rom(0x200, &ROM).unwrap();
+    for _ in 0..STEPS {
+        let _ = cpu.step();
+    }
+    cpu.v[0]
+}
+
+fn step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step");
+    group.bench_function("decode every step", |b| b.iter(|| run(black_box(false))));
+    group.bench_function("decode cache", |b| b.iter(|| run(black_box(true))));
+    group.finish();
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Runs arbitrary ROMs with arbitrary input, the core must never panic.
+//!
+//! cargo +nightly fuzz run run_rom
+#![no_main]
+use chip_core::{Cpu, Preset};
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    preset: u8,
+    seed: u32,
+    vip_timing: bool,
+    instructions_per_frame: u8,
+    load_addr: u16,
+    /// pressed keys of each frame as a bitmask
+    frames: Vec<u16>,
+    rom: Vec<u8>
+}
+
+fuzz_target!(|input: Input| {
+    let preset = Preset::ALL[input.preset as usize % Preset::ALL.len()];
+    let mut cpu = Cpu::with_preset(preset);
+    cpu.set_random_seed(input.seed);
+    if cpu.load_rom(input.load_addr, &input.rom).is_err() { return }
+    for bits in input.frames {
+        cpu.set_keys(core::array::from_fn(|i| bits & 1 << i != 0));
+        if input.vip_timing {
+            cpu.run_vip_frame();
+        } else {
+            cpu.run_frame(input.instructions_per_frame as u32);
+        }
+    }
+});
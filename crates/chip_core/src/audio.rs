@@ -0,0 +1,128 @@
+//! Buzzer synthesis.
+//!
+//! The sound timer gates a square wave, band-limited with PolyBLEP so that
+//! its edges do not alias. A short linear envelope fades the tone in and out
+//! instead of cutting it mid-cycle, which would click.
+use crate::cpu::Cpu;
+
+pub const BUZZER_FREQUENCY: f32 = 220.;
+/// peak amplitude, leaving headroom in the [-1, 1] range
+pub const BUZZER_VOLUME: f32 = 0.25;
+pub const ATTACK_SECONDS: f32 = 0.002;
+pub const RELEASE_SECONDS: f32 = 0.01;
+
+/// Oscillator and envelope state, carried over between buffers
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Buzzer {
+    /// position in the current cycle, in [0, 1)
+    phase: f32,
+    gain: f32
+}
+impl Buzzer {
+    pub(crate) fn fill(&mut self, out: &mut [f32], sample_rate: u32, on: bool) {
+        if sample_rate == 0 { return out.fill(0.) }
+        let rate = sample_rate as f32;
+        let step = BUZZER_FREQUENCY / rate;
+        let attack = 1. / (ATTACK_SECONDS * rate);
+        let release = 1. / (RELEASE_SECONDS * rate);
+        for sample in out {
+            self.gain = if on {
+                (self.gain + attack).min(1.)
+            } else {
+                (self.gain - release).max(0.)
+            };
+            if self.gain == 0. {
+                // every tone starts on a rising edge
+                self.phase = 0.;
+                *sample = 0.;
+                continue;
+            }
+            let mut val = if self.phase < 0.5 { 1. } else { -1. };
+            val += poly_blep(self.phase, step);
+            val -= poly_blep(wrap(self.phase + 0.5), step);
+            *sample = val * self.gain * BUZZER_VOLUME;
+            self.phase = wrap(self.phase + step);
+        }
+    }
+}
+
+impl Cpu {
+    /// Renders the buzzer into mono samples in [-1, 1], following the sound timer.
+    /// Called with the samples of each frame, the tone is continuous across calls.
+    pub fn fill_audio(&mut self, out: &mut [f32], sample_rate: u32) {
+        let on = self.beeps();
+        self.buzzer.fill(out, sample_rate, on);
+    }
+}
+
+fn wrap(phase: f32) -> f32 {
+    if phase >= 1. { phase - 1. } else { phase }
+}
+
+/// Correction smoothing a rising step at phase 0, `dt` is the phase per sample
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.
+    } else if t > 1. - dt {
+        let t = (t - 1.) / dt;
+        t * t + t + t + 1.
+    } else {
+        0.
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44100;
+
+    #[test]
+    fn silent_without_timer() {
+        let mut cpu = Cpu::new();
+        cpu.sound_timer = 0;
+        let mut out = [1.; 256];
+        cpu.fill_audio(&mut out, RATE);
+        assert!(out.iter().all(|&s| s == 0.));
+    }
+    #[test]
+    fn envelope() {
+        let mut cpu = Cpu::new();
+        let mut out = [0.; 1024];
+        cpu.fill_audio(&mut out, RATE);
+        // fades in rather than jumping to full volume
+        assert!(out[0].abs() < 0.05 * BUZZER_VOLUME);
+        assert!(out.iter().all(|s| s.abs() <= BUZZER_VOLUME * 1.01));
+        assert!(out.iter().any(|s| s.abs() > 0.9 * BUZZER_VOLUME));
+        // and fades out within the release time
+        cpu.sound_timer = 0;
+        let release = (RELEASE_SECONDS * RATE as f32) as usize + 1;
+        cpu.fill_audio(&mut out[..release], RATE);
+        assert!(out[release / 2].abs() < 0.6 * BUZZER_VOLUME);
+        cpu.fill_audio(&mut out, RATE);
+        assert!(out.iter().all(|&s| s == 0.));
+    }
+    #[test]
+    fn continuous_across_buffers() {
+        let mut whole = [0.; 1000];
+        let mut cpu = Cpu::new();
+        cpu.fill_audio(&mut whole, RATE);
+        let mut split = [0.; 1000];
+        let mut cpu = Cpu::new();
+        let (a, b) = split.split_at_mut(333);
+        cpu.fill_audio(a, RATE);
+        cpu.fill_audio(b, RATE);
+        assert!(whole == split);
+    }
+    #[test]
+    fn band_limited_edges() {
+        let mut buzzer = Buzzer { phase: 0., gain: 1. };
+        let mut out = [0.; 400];
+        buzzer.fill(&mut out, RATE, true);
+        // a naive square would jump by twice the volume between samples
+        let max_jump = out.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0., f32::max);
+        assert!(max_jump < 1.5 * BUZZER_VOLUME);
+        assert!(max_jump > 0.5 * BUZZER_VOLUME);
+    }
+}
@@ -1,30 +1,115 @@
 use crate::{
+    audio::Buzzer,
+    disasm::Instruction,
     display::Display,
-    errors::ChipError,
-    font::FONT,
-    globals::{RAM_SIZE, STACK_SIZE, REG_COUNT, FONT_ADDR, SHIFT_OP_USE_VY},
-    utils::{u8_from_two, u16_from_two, u16_from_three}
+    errors::{ChipError, ErrorKind, ErrorPolicy, OnError},
+    font::{FONT, BIG_FONT},
+    input::KeyQueue,
+    globals::{
+        RAM_SIZE, XO_RAM_SIZE, STACK_SIZE, REG_COUNT, RPL_COUNT, FONT_ADDR, BIG_FONT_ADDR,
+        AUDIO_PATTERN_SIZE, DEFAULT_PITCH, DEFAULT_RANDOM_SEED
+    },
+    quirks::{Platform, Preset, Quirks},
+    random::{RandomSource, XorShift},
+    utils::u16_from_two
 };
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec, vec::Vec};
+
+/// The CXNN source, any `RandomSource` when allocation is available
+#[cfg(feature = "alloc")]
+pub(crate) type Random = Box<dyn RandomSource + Send>;
+#[cfg(not(feature = "alloc"))]
+pub(crate) type Random = XorShift;
+
+#[cfg(feature = "alloc")]
+fn xorshift(seed: u32) -> Random {
+    Box::new(XorShift::new(seed))
+}
+#[cfg(not(feature = "alloc"))]
+fn xorshift(seed: u32) -> Random {
+    XorShift::new(seed)
+}
+
+/// Summary of a 60 Hz frame
+#[derive(Debug, Default, PartialEq)]
+pub struct Frame {
+    /// instructions executed
+    pub steps: u32,
+    /// the display changed during the frame
+    pub redraw: bool,
+    /// the sound timer is still running
+    pub sound: bool,
+    /// run state at the end of the frame
+    pub state: RunState,
+    /// the error that stopped the frame
+    pub error: Option<ChipError>
+}
+
+/// What the machine does on the next step
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum RunState {
+    #[default]
+    Running,
+    /// blocked on FX0A until a key is released, holds the target register
+    WaitingForKey(u8),
+    /// stopped on a fault under `OnError::Halt`
+    Halted(ChipError),
+    /// the program has terminated with 00FD
+    Exited
+}
 
 pub struct Cpu {
-    memory: [u8; RAM_SIZE],
-    display: Display,
+    // sized for XO-CHIP, other platforms use only the first RAM_SIZE bytes
+    pub(crate) memory: [u8; XO_RAM_SIZE],
+    pub(crate) display: Display,
     pub v: [u8; REG_COUNT],
-    pc: u16,
-    i: u16,
-    sp: usize,
-    stack: [u16; STACK_SIZE],
-    delay_timer: u8,
-    sound_timer: u8,
-    keys: [bool; 0x10],
-    prev_keys: [bool; 0x10],
-    random_seed: u32,
-    redraw: bool
+    pub(crate) pc: u16,
+    pub(crate) i: u16,
+    pub(crate) sp: usize,
+    pub(crate) stack: [u16; STACK_SIZE],
+    pub(crate) delay_timer: u8,
+    pub(crate) sound_timer: u8,
+    pub(crate) keys: [bool; 0x10],
+    pub(crate) key_queue: KeyQueue,
+    /// the first key pressed while FX0A waits for a release
+    pub(crate) wait_key: Option<u8>,
+    pub(crate) random: Random,
+    pub(crate) redraw: bool,
+    pub(crate) vblank: bool,
+    pub(crate) quirks: Quirks,
+    pub(crate) platform: Platform,
+    pub(crate) rpl: [u8; RPL_COUNT],
+    pub(crate) state: RunState,
+    pub(crate) error_policy: ErrorPolicy,
+    pub(crate) audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub(crate) pitch: u8,
+    pub(crate) buzzer: Buzzer,
+    // machine cycles left in the current VIP frame
+    pub(crate) vip_budget: i32,
+    // decoded instructions by address, allocated on first use
+    #[cfg(feature = "alloc")]
+    pub(crate) cache: Vec<Option<Instruction>>,
+    #[cfg(feature = "alloc")]
+    pub(crate) cache_enabled: bool
+}
+impl Default for Cpu {
+    fn default() -> Self {
+        Cpu::new()
+    }
 }
 impl Cpu {
     pub fn new() -> Self {
+        Cpu::with_quirks(Quirks::default())
+    }
+    pub fn with_preset(preset: Preset) -> Self {
+        let mut cpu = Cpu::with_quirks(preset.quirks());
+        cpu.platform = preset.platform();
+        cpu
+    }
+    pub fn with_quirks(quirks: Quirks) -> Self {
         let mut cpu = Cpu {
-            memory: [0; RAM_SIZE],
+            memory: [0; XO_RAM_SIZE],
             display: Display::new(),
             v: [0; REG_COUNT],
             pc: 0,
@@ -34,24 +119,55 @@ impl Cpu {
             delay_timer: 0,
             sound_timer: u8::MAX,
             keys: [false; 0x10],
-            prev_keys: [false; 0x10],
-            random_seed: 0x5321a409,
+            key_queue: KeyQueue::default(),
+            wait_key: None,
+            random: xorshift(DEFAULT_RANDOM_SEED),
             redraw: false,
+            vblank: false,
+            quirks,
+            platform: Platform::default(),
+            rpl: [0; RPL_COUNT],
+            state: RunState::Running,
+            error_policy: ErrorPolicy::default(),
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            buzzer: Buzzer::default(),
+            vip_budget: 0,
+            #[cfg(feature = "alloc")]
+            cache: Vec::new(),
+            #[cfg(feature = "alloc")]
+            cache_enabled: true
         };
         cpu.load(FONT_ADDR, &FONT);
+        cpu.load(BIG_FONT_ADDR, &BIG_FONT);
         cpu
     }
-    pub fn load_rom(&mut self, addr: u16, data: &[u8]) {
+    /// Copies the program to memory and jumps to it,
+    /// fails if it does not fit the platform's RAM
+    pub fn load_rom(&mut self, addr: u16, data: &[u8]) -> Result<(), ErrorKind> {
+        if addr as usize + data.len() > self.ram_size() {
+            return Err(ErrorKind::RomTooLarge(data.len()));
+        }
         self.load(addr, data);
         self.pc = addr;
+        Ok(())
     }
     fn load(&mut self, addr: u16, data: &[u8]) {
         let end = addr as usize + data.len();
         self.memory[addr as usize..end].copy_from_slice(data);
+        self.invalidate(addr as usize, data.len());
     }
     pub fn get_display_buffer(&self) -> &[u8] {
         self.display.get_buffer()
     }
+    /// Returns a single bitplane, None if it does not exist
+    pub fn get_plane_buffer(&self, plane: usize) -> Option<&[u8]> {
+        self.display.get_plane_buffer(plane)
+    }
+    /// Current resolution as (width, height)
+    pub fn get_display_size(&self) -> (usize, usize) {
+        (self.display.width(), self.display.height())
+    }
     /// Checks and clears the redraw flag
     pub fn take_redraw(&mut self) -> bool {
         if self.redraw {
@@ -60,209 +176,500 @@ impl Cpu {
         }
         false
     }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        self.clear_cache();
+    }
+    /// Addressable memory on the current platform
+    pub fn ram_size(&self) -> usize {
+        match self.platform {
+            Platform::XoChip => XO_RAM_SIZE,
+            _ => RAM_SIZE
+        }
+    }
+    /// XO-CHIP 1-bit audio pattern, played back MSB first
+    pub fn audio_pattern(&self) -> &[u8; AUDIO_PATTERN_SIZE] {
+        &self.audio_pattern
+    }
+    /// XO-CHIP pitch register.
+    /// The pattern playback rate is 4000 * 2 ^ ((pitch - 64) / 48) bits per second
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+    pub fn run_state(&self) -> &RunState {
+        &self.state
+    }
+    /// Whether the program has terminated with 00FD
+    pub fn exited(&self) -> bool {
+        self.state == RunState::Exited
+    }
     // set initial state for the XORshift
     pub fn set_random_seed(&mut self, val: u32) {
-        self.random_seed = val;
+        self.random = xorshift(val);
+    }
+    /// Seed of the random source, to be logged for reproducing a run
+    pub fn random_seed(&self) -> u32 {
+        self.random.seed()
     }
-    pub fn set_keys(&mut self, keys: [bool; 0x10]) {
-        self.prev_keys = self.keys;
-        self.keys = keys;
+    #[cfg(feature = "alloc")]
+    pub fn set_random_source(&mut self, source: impl RandomSource + Send + 'static) {
+        self.random = Box::new(source);
     }
     pub fn decrease_timers(&mut self) {
         self.delay_timer = self.delay_timer.saturating_sub(1);
         self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.vblank = true;
     }
     pub fn beeps(&self) -> bool {
         self.sound_timer > 0
     }
+    /// Runs a frame of `instructions` steps and ticks the timers once.
+    /// Stops early on an error, exit or halt, consumes the redraw flag.
+    pub fn run_frame(&mut self, instructions: u32) -> Frame {
+        let mut frame = Frame::default();
+        while frame.steps < instructions && self.ready() {
+            if let Err(e) = self.step() {
+                frame.error = Some(e);
+                break;
+            }
+            frame.steps += 1;
+        }
+        self.decrease_timers();
+        self.end_frame(frame)
+    }
+    pub(crate) fn end_frame(&mut self, mut frame: Frame) -> Frame {
+        frame.redraw = self.take_redraw();
+        frame.sound = self.beeps();
+        frame.state = self.state.clone();
+        frame
+    }
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.error_policy
+    }
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+    /// The fault the machine is halted on
+    pub fn halted(&self) -> Option<&ChipError> {
+        match &self.state {
+            RunState::Halted(error) => Some(error),
+            _ => None
+        }
+    }
+    /// Resumes a halted machine at the current PC
+    pub fn resume(&mut self) {
+        if self.halted().is_some() { self.state = RunState::Running }
+    }
     pub fn step(&mut self) -> Result<(), ChipError> {
-        let op = self.get_current_opcode()?;
-        self.pc += 2;
-        match op {
-            (0, 0, 0xE, 0) => {
+        match self.state {
+            RunState::Halted(ref error) => return Err(error.clone()),
+            RunState::Exited => return Ok(()),
+            _ => ()
+        }
+        let waiting = self.state != RunState::Running;
+        self.apply_key_event();
+        if waiting { return Ok(()) }
+        let pc = self.pc;
+        let (size, result) = match self.decode() {
+            Ok(inst) => (inst.size(), self.execute(inst)),
+            Err(kind) => (2, Err(kind))
+        };
+        let Err(kind) = result else { return Ok(()) };
+        let error = ChipError { kind, pc, opcode: self.fetch_at(pc).ok() };
+        match self.error_policy.get(kind) {
+            OnError::Halt => {
+                self.pc = pc;
+                self.state = RunState::Halted(error.clone());
+                Err(error)
+            },
+            OnError::Skip => {
+                self.pc = pc.wrapping_add(size as u16);
+                Ok(())
+            },
+            OnError::Trap => {
+                self.pc = pc.wrapping_add(size as u16);
+                Err(error)
+            }
+        }
+    }
+    fn execute(&mut self, inst: Instruction) -> Result<(), ErrorKind> {
+        self.pc = self.pc.wrapping_add(2);
+        match inst {
+            Instruction::ScrollDown(n) => {
+                self.display.scroll_down(n as usize);
+                self.redraw = true;
+            },
+            Instruction::ScrollUp(n) => {
+                self.display.scroll_up(n as usize);
+                self.redraw = true;
+            },
+            Instruction::Cls => {
                 self.display.clear();
                 self.redraw = true;
             },
-            (0, 0, 0xE, 0xE) => self.pc = self.pop_stack()?,
+            Instruction::Ret => self.pc = self.pop_stack()?,
+            Instruction::ScrollRight => {
+                self.display.scroll_right();
+                self.redraw = true;
+            },
+            Instruction::ScrollLeft => {
+                self.display.scroll_left();
+                self.redraw = true;
+            },
+            Instruction::Exit => {
+                self.pc = self.pc.wrapping_sub(2);
+                self.state = RunState::Exited;
+            },
+            Instruction::Lores => {
+                self.display.set_hires(false);
+                self.redraw = true;
+            },
+            Instruction::Hires => {
+                self.display.set_hires(true);
+                self.redraw = true;
+            },
             // machine subroutine -> ignored
-            (0, _, _, _) => (),
-            (1, n0, n1, n2) => self.pc = u16_from_three(n0, n1, n2),
-            (2, n0, n1, n2) => {
+            Instruction::Sys(_) => (),
+            Instruction::Jump(nnn) => self.pc = nnn,
+            Instruction::Call(nnn) => {
                 self.push_stack(self.pc)?;
-                self.pc = u16_from_three(n0, n1, n2);
+                self.pc = nnn;
             },
-            (3, x, n0, n1) => if *(self.get_reg(x)?) == u8_from_two(n0, n1) {
-                self.pc += 2;
+            Instruction::SkipEqImm(x, nn) => if *(self.get_reg(x)?) == nn {
+                self.skip();
             },
-            (4, x, n0, n1) => if *(self.get_reg(x)?) != u8_from_two(n0, n1) {
-                self.pc += 2;
+            Instruction::SkipNeImm(x, nn) => if *(self.get_reg(x)?) != nn {
+                self.skip();
             },
-            (5, x, y, 0) => if self.get_reg(x)? == self.get_reg(y)? {
-                self.pc += 2;
+            Instruction::SkipEq(x, y) => if self.get_reg(x)? == self.get_reg(y)? {
+                self.skip();
             },
-            (6, x, n0, n1) => self.set_reg(x, u8_from_two(n0, n1))?,
-            (7, x, n0, n1) => {
-                let val = self.get_reg(x)?.wrapping_add(u8_from_two(n0, n1));
+            Instruction::SaveRange(x, y) => {
+                for (offset, r) in reg_range(x, y).enumerate() {
+                    self.write(self.i as usize + offset, *self.get_reg(r)?)?;
+                }
+            },
+            Instruction::LoadRange(x, y) => {
+                for (offset, r) in reg_range(x, y).enumerate() {
+                    self.set_reg(r, self.read(self.i as usize + offset)?)?;
+                }
+            },
+            Instruction::SetImm(x, nn) => self.set_reg(x, nn)?,
+            Instruction::AddImm(x, nn) => {
+                let val = self.get_reg(x)?.wrapping_add(nn);
                 self.set_reg(x, val)?;
             },
-            (8, x, y, 0) => self.set_reg(x, *self.get_reg(y)?)?,
-            (8, x, y, 1) => self.set_reg(x, self.get_reg(x)? | self.get_reg(y)?)?,
-            (8, x, y, 2) => self.set_reg(x, self.get_reg(x)? & self.get_reg(y)?)?,
-            (8, x, y, 3) => self.set_reg(x, self.get_reg(x)? ^ self.get_reg(y)?)?,
-            (8, x, y, 4) => {
+            Instruction::Set(x, y) => self.set_reg(x, *self.get_reg(y)?)?,
+            Instruction::Or(x, y) => {
+                self.set_reg(x, self.get_reg(x)? | self.get_reg(y)?)?;
+                if self.quirks.logic_reset_vf { self.set_flag(false) }
+            },
+            Instruction::And(x, y) => {
+                self.set_reg(x, self.get_reg(x)? & self.get_reg(y)?)?;
+                if self.quirks.logic_reset_vf { self.set_flag(false) }
+            },
+            Instruction::Xor(x, y) => {
+                self.set_reg(x, self.get_reg(x)? ^ self.get_reg(y)?)?;
+                if self.quirks.logic_reset_vf { self.set_flag(false) }
+            },
+            Instruction::Add(x, y) => {
                 let (val, overflow) = self.get_reg(x)?.overflowing_add(*self.get_reg(y)?);
                 self.set_reg(x, val)?;
                 self.set_flag(overflow);
             },
-            (8, x, y, 5) => {
+            Instruction::Sub(x, y) => {
                 let (val, overflow) = self.get_reg(x)?.overflowing_sub(*self.get_reg(y)?);
                 self.set_reg(x, val)?;
                 self.set_flag(!overflow);
             },
-            (8, x, y, 6) => {
-                let val = *self.get_reg(if SHIFT_OP_USE_VY {y} else {x})?;
+            Instruction::Shr(x, y) => {
+                let val = *self.get_reg(if self.quirks.shift_vy {y} else {x})?;
                 self.set_reg(x, val >> 1)?;
                 self.set_flag(val & 1 == 1);
             },
-            (8, x, y, 7) => {
+            Instruction::SubN(x, y) => {
                 let (val, overflow) = self.get_reg(y)?.overflowing_sub(*self.get_reg(x)?);
                 self.set_reg(x, val)?;
                 self.set_flag(!overflow);
             },
-            (8, x, y, 0xE) => {
-                let val = *self.get_reg(if SHIFT_OP_USE_VY {y} else {x})?;
+            Instruction::Shl(x, y) => {
+                let val = *self.get_reg(if self.quirks.shift_vy {y} else {x})?;
                 self.set_reg(x, val << 1)?;
                 self.set_flag(val >> 7 == 1);
             },
-            (9, x, y, 0) => if self.get_reg(x)? != self.get_reg(y)? {
-                self.pc += 2;
+            Instruction::SkipNe(x, y) => if self.get_reg(x)? != self.get_reg(y)? {
+                self.skip();
             },
-            (0xA, n0, n1, n2) => {
-                self.i = u16_from_three(n0, n1, n2);
+            Instruction::SetI(nnn) => {
+                self.i = nnn;
             },
-            (0xB, n0, n1, n2) => {
-                self.pc = u16_from_three(n0, n1, n2) + self.v[0] as u16;
+            Instruction::JumpOffset(nnn) => {
+                let offset = *self.get_reg(if self.quirks.jump_vx {(nnn >> 8) as u8} else {0})?;
+                let addr = nnn + offset as u16;
+                if addr as usize >= self.ram_size() { return Err(ErrorKind::IllegalAddr(addr)) }
+                self.pc = addr;
             },
-            (0xC, x, n0, n1) => {
+            Instruction::Random(x, nn) => {
                 let r = self.random();
-                self.set_reg(x, r & u8_from_two(n0, n1))?;
+                self.set_reg(x, r & nn)?;
             },
-            (0xD, x, y, n) => {
-                if self.i + n as u16 >= self.memory.len() as u16 {
-                    return Err(ChipError::IllegalAddr(self.i + n as u16));
+            Instruction::Draw(x, y, n) => {
+                if self.quirks.display_wait && !self.vblank {
+                    // wait for the next frame
+                    self.pc = self.pc.wrapping_sub(2);
+                    return Ok(());
+                }
+                self.vblank = false;
+                // DXY0 draws a 16x16 sprite on the extended platforms
+                let wide = n == 0 && self.extended();
+                let len = if wide { 32 } else { n as usize };
+                // each selected plane consumes its own sprite data
+                let size = len * self.display.selected().count_ones() as usize;
+                if self.i as usize + size > self.ram_size() {
+                    return Err(ErrorKind::IllegalAddr(self.i.wrapping_add(size as u16)));
+                }
+                let vx = *self.get_reg(x)? as usize;
+                let vy = *self.get_reg(y)? as usize;
+                let wrap = self.quirks.wrap_sprites;
+                let data = &self.memory[self.i as usize..self.i as usize + size];
+                let rows = if wide {
+                    self.display.blit_sprite_16(vx, vy, data, wrap)
+                } else {
+                    self.display.blit_sprite(vx, vy, data, len, wrap)
+                };
+                if self.platform == Platform::SuperChip && self.display.is_hires() {
+                    // SCHIP reports the number of rows that collided or got clipped
+                    let lines = if wide { 16 } else { len };
+                    let height = self.display.height();
+                    let clipped = if wrap { 0 } else { (vy % height + lines).saturating_sub(height) };
+                    self.v[0xF] = rows + clipped as u8;
+                } else {
+                    self.set_flag(rows != 0);
                 }
-                let data = &self.memory[self.i as usize..self.i as usize + n as usize];
-                let flag = self.display.blit_sprite(
-                    *self.get_reg(x)? as usize,
-                    *self.get_reg(y)? as usize,
-                    data,
-                    n as usize
-                );
-                self.set_flag(flag != 0);
                 self.redraw = true;
             },
-            (0xE, x, 9, 0xE) => if *self.get_key(*self.get_reg(x)?)? { self.pc += 2 },
-            (0xE, x, 0xA, 1) => if !*self.get_key(*self.get_reg(x)?)? { self.pc += 2 },
-            (0xF, x, 0, 7) => self.set_reg(x, self.delay_timer)?,
-            (0xF, x, 0, 0xA) => {
-                // detect a release
-                if let Some(pressed) = self.prev_keys.iter().enumerate().find(|(i, a)| **a && !self.keys[*i]) {
-                    self.set_reg(x, pressed.0 as u8)?;
-                } else {
-                    self.pc -= 2;
+            Instruction::SkipKey(x) => if *self.get_key(*self.get_reg(x)?)? { self.skip() },
+            Instruction::SkipNotKey(x) => if !*self.get_key(*self.get_reg(x)?)? { self.skip() },
+            Instruction::LongI => {
+                // long I load, NNNN is stored in the following word
+                let addr = self.pc as usize;
+                if addr + 1 >= self.ram_size() {
+                    return Err(ErrorKind::IllegalAddr(self.pc));
                 }
+                self.i = u16_from_two(self.memory[addr], self.memory[addr + 1]);
+                self.pc = self.pc.wrapping_add(2);
             },
-            (0xF, x, 1, 5) => self.delay_timer = *self.get_reg(x)?,
-            (0xF, x, 1, 8) => self.sound_timer = *self.get_reg(x)?,
-            (0xF, x, 1, 0xE) => self.i = self.i.wrapping_add(*self.get_reg(x)? as u16),
-            (0xF, x, 2, 9) => self.i = FONT_ADDR + *self.get_reg(x)? as u16,
-            (0xF, x, 3, 3) => {
+            Instruction::Planes(n) => self.display.select_planes(n),
+            Instruction::Audio => {
+                let start = self.i as usize;
+                if start + AUDIO_PATTERN_SIZE > self.ram_size() {
+                    return Err(ErrorKind::IllegalAddr(self.i));
+                }
+                self.audio_pattern.copy_from_slice(&self.memory[start..start + AUDIO_PATTERN_SIZE]);
+            },
+            Instruction::GetDelay(x) => self.set_reg(x, self.delay_timer)?,
+            Instruction::WaitKey(x) => {
+                self.get_reg(x)?;
+                self.wait_key = None;
+                self.state = RunState::WaitingForKey(x);
+            },
+            Instruction::SetDelay(x) => self.delay_timer = *self.get_reg(x)?,
+            Instruction::SetSound(x) => self.sound_timer = *self.get_reg(x)?,
+            Instruction::AddI(x) => self.i = self.i.wrapping_add(*self.get_reg(x)? as u16),
+            Instruction::Font(x) => self.i = FONT_ADDR + *self.get_reg(x)? as u16,
+            Instruction::BigFont(x) => {
+                self.i = BIG_FONT_ADDR + 10 * (*self.get_reg(x)? as u16 & 0xF);
+            },
+            Instruction::Pitch(x) => self.pitch = *self.get_reg(x)?,
+            Instruction::Bcd(x) => {
                 let val = *self.get_reg(x)?;
-                self.memory[self.i as usize] = val / 100;
-                self.memory[self.i as usize + 1] = val % 100 / 10;
-                self.memory[self.i as usize + 2] = val % 10;
+                self.write(self.i as usize, val / 100)?;
+                self.write(self.i as usize + 1, val % 100 / 10)?;
+                self.write(self.i as usize + 2, val % 10)?;
             },
-            (0xF, x, 5, 5) => {
+            Instruction::Store(x) => {
                 for t in 0..=x {
-                    self.memory[self.i as usize + t as usize] = *self.get_reg(t)?;
+                    self.write(self.i as usize + t as usize, *self.get_reg(t)?)?;
                 }
+                if self.quirks.memory_increment_i { self.i = self.i.wrapping_add(x as u16 + 1) }
             },
-            (0xF, x, 6, 5) => {
+            Instruction::Load(x) => {
                 for t in 0..=x {
-                    self.set_reg(t, self.memory[self.i as usize + t as usize])?;
+                    self.set_reg(t, self.read(self.i as usize + t as usize)?)?;
                 }
+                if self.quirks.memory_increment_i { self.i = self.i.wrapping_add(x as u16 + 1) }
             },
-            _ => return Err(ChipError::IllegalInst(u16_from_two(
-                self.memory[self.pc as usize - 2],
-                self.memory[self.pc as usize - 1]
-            ))),
+            Instruction::SaveFlags(x) => {
+                for t in 0..=x {
+                    *self.rpl.get_mut(t as usize).ok_or(ErrorKind::IllegalReg(t))? = *self.get_reg(t)?;
+                }
+            },
+            Instruction::LoadFlags(x) => {
+                for t in 0..=x {
+                    self.set_reg(t, *self.rpl.get(t as usize).ok_or(ErrorKind::IllegalReg(t))?)?;
+                }
+            },
+            Instruction::Unknown(word) => return Err(ErrorKind::IllegalInst(word)),
         };
         Ok(())
     }
-    fn get_current_opcode(&self) -> Result<(u8, u8, u8, u8), ChipError> {
+    /// Decodes the instruction at PC
+    pub fn current_instruction(&self) -> Result<Instruction, ChipError> {
+        self.fetch()
+            .map(|word| Instruction::decode(word, self.platform))
+            .map_err(|kind| ChipError { kind, pc: self.pc, opcode: None })
+    }
+    /// Enables the decoded instruction cache, on by default.
+    /// Without the `alloc` feature every step decodes.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        #[cfg(feature = "alloc")]
+        {
+            self.cache_enabled = enabled;
+            self.clear_cache();
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = enabled;
+    }
+    #[cfg(feature = "alloc")]
+    fn decode(&mut self) -> Result<Instruction, ErrorKind> {
+        let addr = self.pc as usize;
+        if let Some(Some(inst)) = self.cache.get(addr) { return Ok(*inst) }
+        let inst = Instruction::decode(self.fetch()?, self.platform);
+        if self.cache_enabled {
+            if self.cache.is_empty() { self.cache = vec![None; XO_RAM_SIZE] }
+            self.cache[addr] = Some(inst);
+        }
+        Ok(inst)
+    }
+    #[cfg(not(feature = "alloc"))]
+    fn decode(&mut self) -> Result<Instruction, ErrorKind> {
+        Ok(Instruction::decode(self.fetch()?, self.platform))
+    }
+    /// Drops decoded instructions overlapping the memory range
+    pub(crate) fn invalidate(&mut self, addr: usize, len: usize) {
+        #[cfg(feature = "alloc")]
+        if !self.cache.is_empty() {
+            // an instruction starting a byte earlier spans the first address
+            let end = (addr + len).min(XO_RAM_SIZE);
+            self.cache[addr.saturating_sub(1)..end].fill(None);
+        }
+        #[cfg(not(feature = "alloc"))]
+        let _ = (addr, len);
+    }
+    pub(crate) fn clear_cache(&mut self) {
+        #[cfg(feature = "alloc")]
+        self.cache.fill(None);
+    }
+    fn read(&self, addr: usize) -> Result<u8, ErrorKind> {
+        if addr >= self.ram_size() { return Err(ErrorKind::IllegalAddr(addr as u16)) }
+        Ok(self.memory[addr])
+    }
+    pub(crate) fn write(&mut self, addr: usize, val: u8) -> Result<(), ErrorKind> {
+        if addr >= self.ram_size() { return Err(ErrorKind::IllegalAddr(addr as u16)) }
+        self.memory[addr] = val;
+        self.invalidate(addr, 1);
+        Ok(())
+    }
+    fn fetch(&self) -> Result<u16, ErrorKind> {
+        self.fetch_at(self.pc)
+    }
+    fn fetch_at(&self, pc: u16) -> Result<u16, ErrorKind> {
+        let addr = pc as usize;
+        if addr + 2 > self.ram_size() {
+            return Err(ErrorKind::IllegalAddr(pc))
+        }
+        Ok(u16_from_two(self.memory[addr], self.memory[addr + 1]))
+    }
+    /// SCHIP instructions are available
+    pub(crate) fn extended(&self) -> bool {
+        self.platform != Platform::Chip8
+    }
+    /// XO-CHIP instructions are available
+    fn xo(&self) -> bool {
+        self.platform == Platform::XoChip
+    }
+    /// skips the next instruction
+    fn skip(&mut self) {
+        // F000 NNNN is four bytes long
         let addr = self.pc as usize;
-        if addr > RAM_SIZE - 2 {
-            return Err(ChipError::IllegalAddr(self.pc))
+        if self.xo() && addr + 1 < self.ram_size()
+            && self.memory[addr] == 0xF0 && self.memory[addr + 1] == 0x00 {
+            self.pc = self.pc.wrapping_add(2);
         }
-        Ok((
-            self.memory[addr] >> 4,
-            self.memory[addr] & 0x0F ,
-            self.memory[addr + 1] >> 4,
-            self.memory[addr + 1] & 0x0F,
-        ))
-    }
-    fn get_reg(&self, i: u8) -> Result<&u8, ChipError> {
-        self.v.get(i as usize).ok_or(ChipError::IllegalReg(i))
-    }
-    fn set_reg(&mut self, i: u8, val: u8) -> Result<(), ChipError> {
-        *(self.v.get_mut(i as usize).ok_or(ChipError::IllegalReg(i))?) = val; 
+        self.pc = self.pc.wrapping_add(2);
+    }
+    fn get_reg(&self, i: u8) -> Result<&u8, ErrorKind> {
+        self.v.get(i as usize).ok_or(ErrorKind::IllegalReg(i))
+    }
+    fn set_reg(&mut self, i: u8, val: u8) -> Result<(), ErrorKind> {
+        *(self.v.get_mut(i as usize).ok_or(ErrorKind::IllegalReg(i))?) = val; 
         Ok(())
     }
-    fn get_key(&self, i: u8) -> Result<&bool, ChipError> {
-        self.keys.get(i as usize).ok_or(ChipError::IllegalKey(i))
+    fn get_key(&self, i: u8) -> Result<&bool, ErrorKind> {
+        self.keys.get(i as usize).ok_or(ErrorKind::IllegalKey(i))
     }
-    fn push_stack(&mut self, val: u16) -> Result<(), ChipError> {
+    fn push_stack(&mut self, val: u16) -> Result<(), ErrorKind> {
+        if self.sp >= STACK_SIZE { return Err(ErrorKind::StackOverflow) };
         self.stack[self.sp] = val;
         self.sp += 1;
-        if self.sp >= STACK_SIZE { return Err(ChipError::StackOverflow) };
         Ok(())
     }
-    fn pop_stack(&mut self) -> Result<u16, ChipError> {
-        if self.sp == 0 { return Err(ChipError::StackUnderflow) }
+    fn pop_stack(&mut self) -> Result<u16, ErrorKind> {
+        if self.sp == 0 { return Err(ErrorKind::StackUnderflow) }
         self.sp -= 1;
         Ok(self.stack[self.sp])
     }
+    /// The next step makes progress, false while blocked on input
+    pub(crate) fn ready(&self) -> bool {
+        match self.state {
+            RunState::Running => true,
+            RunState::WaitingForKey(_) => !self.key_queue.is_empty(),
+            _ => false
+        }
+    }
     fn set_flag(&mut self, val: bool) {
         self.v[0xF] = if val { 1 } else { 0 };
     }
     fn random(&mut self) -> u8 {
-        let mut val = self.random_seed;
-        val ^= val << 13;
-        val ^= val >> 17;
-        val ^= val << 5;
-        self.random_seed = val;
-        val as u8
+        self.random.next_byte()
     }
 }
 
+/// registers from x to y, in either direction
+fn reg_range(x: u8, y: u8) -> impl Iterator<Item=u8> {
+    let (lo, hi) = if x <= y { (x, y) } else { (y, x) };
+    (lo..=hi).map(move |r| if x <= y { r } else { hi + lo - r })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::quirks::Preset;
     #[test]
     fn get_opcode() {
         let mut cpu = Cpu::new();
         cpu.pc = 0x200;
         cpu.memory[0x200] = 0xA4;
         cpu.memory[0x201] = 0xC3;
-        assert!(cpu.get_current_opcode() == Ok((0xA, 0x4, 0xC, 0x3)));
+        assert!(cpu.fetch() == Ok(0xA4C3));
+        assert!(cpu.current_instruction() == Ok(Instruction::SetI(0x4C3)));
     }
     #[test]
     fn get_opcode_illegal_addr() {
         let mut cpu = Cpu::new();
         cpu.pc = RAM_SIZE as u16 - 1;
-        assert!(cpu.get_current_opcode() == Err(ChipError::IllegalAddr(cpu.pc)));
+        assert!(cpu.fetch() == Err(ErrorKind::IllegalAddr(cpu.pc)));
         cpu.pc = RAM_SIZE as u16;
-        assert!(cpu.get_current_opcode() == Err(ChipError::IllegalAddr(cpu.pc)));
+        assert!(cpu.fetch() == Err(ErrorKind::IllegalAddr(cpu.pc)));
     }
 
     // OPCODES
@@ -275,7 +682,7 @@ mod tests {
         cpu.memory[0x200] = 0x00;
         cpu.memory[0x201] = 0xE0;
         let _ = cpu.step();
-        assert!(cpu.display.get_buffer() == &[0u8; crate::globals::SCREEN_BUFFER_SIZE]);
+        assert!(cpu.display.get_buffer() == [0u8; crate::globals::SCREEN_BUFFER_SIZE]);
         assert!(cpu.pc == 0x202);
     }
     #[test]
@@ -517,7 +924,7 @@ mod tests {
         cpu.memory[0x200] = 0x85;
         cpu.memory[0x201] = 0xA6;
         let _ = cpu.step();
-        if SHIFT_OP_USE_VY {
+        if cpu.quirks.shift_vy {
             assert!(cpu.v[5] == 0b01001100);
         } else {
             assert!(cpu.v[5] == 0b01001110);
@@ -535,7 +942,7 @@ mod tests {
         cpu.memory[0x200] = 0x85;
         cpu.memory[0x201] = 0xA6;
         let _ = cpu.step();
-        if SHIFT_OP_USE_VY {
+        if cpu.quirks.shift_vy {
             assert!(cpu.v[5] == 0b01001100);
         } else {
             assert!(cpu.v[5] == 0b01001110);
@@ -579,7 +986,7 @@ mod tests {
         cpu.memory[0x200] = 0x85;
         cpu.memory[0x201] = 0xAE;
         let _ = cpu.step();
-        if SHIFT_OP_USE_VY {
+        if cpu.quirks.shift_vy {
             assert!(cpu.v[5] == 0b00110010);
         } else {
             assert!(cpu.v[5] == 0b00111010);
@@ -597,7 +1004,7 @@ mod tests {
         cpu.memory[0x200] = 0x85;
         cpu.memory[0x201] = 0xAE;
         let _ = cpu.step();
-        if SHIFT_OP_USE_VY {
+        if cpu.quirks.shift_vy {
             assert!(cpu.v[5] == 0b00110010);
         } else {
             assert!(cpu.v[5] == 0b00111010);
@@ -653,7 +1060,7 @@ mod tests {
     fn op_cxnn() {
         // testing random result ;)
         let mut cpu = Cpu::new();
-        cpu.random_seed = 0x12325a5d;
+        cpu.set_random_seed(0x12325a5d);
         cpu.pc = 0x200;
         cpu.v[2] = 0x00;
         cpu.memory[0x200] = 0xc2;
@@ -662,6 +1069,19 @@ mod tests {
         assert!(cpu.v[2] != 0);
         assert!(cpu.pc == 0x202);
     }
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn op_cxnn_scripted() {
+        let mut cpu = Cpu::new();
+        cpu.set_random_source(crate::random::Sequence::new(&[0xff, 0x3c]));
+        // RND V0 0x0F, RND V1 0xF0
+        cpu.load_rom(0x200, &[0xc0, 0x0f, 0xc1, 0xf0]).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.v[0] == 0x0f);
+        assert!(cpu.v[1] == 0x30);
+        assert!(cpu.random_seed() == 0);
+    }
     #[test]
     fn op_dxyn() {
         // based on I drawing from the IBM logo rom
@@ -680,7 +1100,7 @@ mod tests {
         ];
         rom[0x0..0xA].copy_from_slice(&ins);
         rom[0x2a..0x39].copy_from_slice(&data);
-        cpu.load_rom(0x200, &rom);
+        cpu.load_rom(0x200, &rom).unwrap();
         for _ in 0..5 {
             let _ = cpu.step();
         }
@@ -714,7 +1134,7 @@ mod tests {
         rom[0x0..0x8].copy_from_slice(&ins);
         // sprite data
         rom[0x20] = 0b11011101;
-        cpu.load_rom(0x200, &rom);
+        cpu.load_rom(0x200, &rom).unwrap();
         for _ in 0..4 {
             let _ = cpu.step();
         }
@@ -786,19 +1206,31 @@ mod tests {
         cpu.memory[0x200] = 0xf5;
         cpu.memory[0x201] = 0x0a;
         let _ = cpu.step();
-        assert!(cpu.pc == 0x0200);
+        assert!(cpu.pc == 0x0202);
+        assert!(cpu.run_state() == &RunState::WaitingForKey(5));
         assert!(cpu.v[5] == 0x00);
+        // blocked until a key goes up
+        let _ = cpu.step();
+        cpu.key_down(0xb).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.run_state() == &RunState::WaitingForKey(5));
+        cpu.key_up(0xb).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.run_state() == &RunState::Running);
+        assert!(cpu.pc == 0x0202);
+        assert!(cpu.v[5] == 0x0b);
     }
     #[test]
     fn op_fx0a_go() {
         let mut cpu = Cpu::new();
-        cpu.keys = [false; 0x10];
-        cpu.prev_keys[7] = true;
-        cpu.keys[7] = false;
         cpu.pc = 0x200;
         cpu.memory[0x200] = 0xf5;
         cpu.memory[0x201] = 0x0a;
         let _ = cpu.step();
+        cpu.key_down(7).unwrap();
+        cpu.key_up(7).unwrap();
+        let _ = cpu.step();
+        let _ = cpu.step();
         assert!(cpu.pc == 0x0202);
         assert!(cpu.v[5] == 0x07);
     }
@@ -932,4 +1364,481 @@ mod tests {
         assert!(cpu.v[0] == 0xcc);
         assert!(cpu.v[1] == 0x00);
     }
+
+    // FRAMES
+
+    #[test]
+    fn run_frame_ticks_timers_once() {
+        let mut cpu = Cpu::new();
+        cpu.delay_timer = 10;
+        cpu.sound_timer = 1;
+        cpu.load_rom(0x200, &[0x60, 0x01, 0x12, 0x00]).unwrap();
+        let frame = cpu.run_frame(8);
+        // the last sound tick has elapsed
+        assert!(frame == Frame { steps: 8, ..Default::default() });
+        assert!(cpu.delay_timer == 9);
+    }
+    #[test]
+    fn run_frame_summary() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.sound_timer = 5;
+        cpu.load_rom(0x200, &[0xd0, 0x01, 0x00, 0xfd, 0x12, 0x00]).unwrap();
+        let frame = cpu.run_frame(8);
+        assert!(frame == Frame { steps: 2, redraw: true, sound: true, state: RunState::Exited, error: None });
+        // the redraw flag is consumed
+        assert!(!cpu.take_redraw());
+    }
+    #[test]
+    fn run_frame_blocks_on_key_wait() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x60, 0x01, 0xf1, 0x0a, 0x70, 0x01, 0x12, 0x04]).unwrap();
+        let frame = cpu.run_frame(8);
+        assert!(frame.steps == 2);
+        assert!(frame.state == RunState::WaitingForKey(1));
+        assert!(cpu.run_frame(8).steps == 0);
+        let mut keys = [false; 0x10];
+        keys[3] = true;
+        cpu.set_keys(keys);
+        cpu.set_keys([false; 0x10]);
+        // the first step completes FX0A
+        let frame = cpu.run_frame(8);
+        assert!(frame.steps == 8);
+        assert!(frame.state == RunState::Running);
+        assert!(cpu.v[1] == 3);
+    }
+    #[test]
+    fn run_frame_stops_on_error() {
+        let mut cpu = Cpu::new();
+        cpu.delay_timer = 10;
+        cpu.load_rom(0x200, &[0x60, 0x01, 0x50, 0x01]).unwrap();
+        let frame = cpu.run_frame(8);
+        assert!(frame.steps == 1);
+        assert!(frame.error == Some(ChipError { kind: ErrorKind::IllegalInst(0x5001), pc: 0x202, opcode: Some(0x5001) }));
+        assert!(cpu.delay_timer == 9);
+    }
+
+    // ERROR POLICY
+
+    #[test]
+    fn halt_stays_on_fault() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x50, 0x01, 0x60, 0x05]).unwrap();
+        let error = ChipError { kind: ErrorKind::IllegalInst(0x5001), pc: 0x200, opcode: Some(0x5001) };
+        assert!(cpu.step() == Err(error.clone()));
+        assert!(cpu.pc == 0x200);
+        assert!(cpu.halted() == Some(&error));
+        assert!(cpu.step() == Err(error.clone()));
+        // a halted machine only ticks the timers
+        let frame = cpu.run_frame(8);
+        assert!(frame == Frame { sound: true, state: RunState::Halted(error.clone()), ..Default::default() });
+        cpu.resume();
+        assert!(cpu.step() == Err(error));
+    }
+    #[test]
+    fn skip_ignores_fault() {
+        let mut cpu = Cpu::new();
+        cpu.set_error_policy(ErrorPolicy::all(OnError::Skip));
+        cpu.load_rom(0x200, &[0x50, 0x01, 0x60, 0x05]).unwrap();
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.v[0] == 0x05);
+        assert!(cpu.halted().is_none());
+    }
+    #[test]
+    fn trap_resumes_after_fault() {
+        let mut cpu = Cpu::new();
+        cpu.set_error_policy(ErrorPolicy::all(OnError::Trap));
+        cpu.load_rom(0x200, &[0x00, 0xee, 0x60, 0x05]).unwrap();
+        assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::StackUnderflow));
+        assert!(cpu.pc == 0x202);
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.v[0] == 0x05);
+    }
+    #[test]
+    fn policy_per_error() {
+        let mut cpu = Cpu::new();
+        cpu.set_error_policy(ErrorPolicy { illegal_inst: OnError::Skip, ..Default::default() });
+        cpu.load_rom(0x200, &[0x50, 0x01, 0x00, 0xee]).unwrap();
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::StackUnderflow));
+        assert!(cpu.halted().is_some());
+    }
+    #[test]
+    fn fetch_fault_has_no_opcode() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0xfff;
+        let error = ChipError { kind: ErrorKind::IllegalAddr(0xfff), pc: 0xfff, opcode: None };
+        assert!(cpu.step() == Err(error));
+    }
+
+    // HOSTILE ROMS
+
+    #[test]
+    fn memory_ops_past_ram_end() {
+        // FX33, FX55 and FX65 with I at the last byte
+        for op in [0x33, 0x55, 0x65] {
+            let mut cpu = Cpu::new();
+            cpu.i = 0xfff;
+            cpu.load_rom(0x200, &[0xf2, op]).unwrap();
+            assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::IllegalAddr(0x1000)));
+        }
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.i = 0xffff;
+        cpu.load_rom(0x200, &[0x50, 0x12]).unwrap();
+        assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::IllegalAddr(0x0000)));
+    }
+    #[test]
+    fn jump_offset_past_ram_end() {
+        let mut cpu = Cpu::new();
+        cpu.v[0] = 0xff;
+        cpu.load_rom(0x200, &[0xbf, 0xff]).unwrap();
+        assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::IllegalAddr(0x10fe)));
+    }
+    #[test]
+    fn load_rom_too_large() {
+        let mut cpu = Cpu::new();
+        assert!(cpu.load_rom(0x200, &[0; 0xe01]) == Err(ErrorKind::RomTooLarge(0xe01)));
+        assert!(cpu.load_rom(0x200, &[0; 0xe00]) == Ok(()));
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        assert!(cpu.load_rom(0xffff, &[0; 2]) == Err(ErrorKind::RomTooLarge(2)));
+    }
+    #[test]
+    fn stack_overflow_keeps_state() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x22, 0x00]).unwrap();
+        for _ in 0..STACK_SIZE {
+            cpu.step().unwrap();
+        }
+        assert!(cpu.step().map_err(|e| e.kind) == Err(ErrorKind::StackOverflow));
+        assert!(cpu.sp == STACK_SIZE);
+        assert!(cpu.stack == [0x202; STACK_SIZE]);
+    }
+    #[test]
+    fn pc_at_top_of_memory() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.load_rom(0xfffe, &[0x60, 0x01]).unwrap();
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.pc == 0);
+    }
+
+    // DECODE CACHE
+
+    #[test]
+    fn cache_self_modifying_code() {
+        // the loop body rewrites its first instruction through FX55
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[
+            0x70, 0x01,
+            0x60, 0x71,
+            0x61, 0x05,
+            0xa2, 0x00,
+            0xf1, 0x55,
+            0x12, 0x00
+        ]).unwrap();
+        for _ in 0..6 {
+            let _ = cpu.step();
+        }
+        assert!(cpu.memory[0x200..0x202] == [0x71, 0x05]);
+        let _ = cpu.step();
+        assert!(cpu.v[1] == 0x0a);
+    }
+    #[test]
+    fn cache_fx33_overwrites_code() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0xa2, 0x05, 0xf0, 0x33, 0x12, 0x00, 0x70, 0x05]).unwrap();
+        let _ = cpu.step();
+        cpu.pc = 0x206;
+        let _ = cpu.step();
+        assert!(cpu.v[0] == 5);
+        // BCD of 5 turns 7005 into 0005, which is ignored
+        cpu.pc = 0x202;
+        let _ = cpu.step();
+        cpu.pc = 0x206;
+        let _ = cpu.step();
+        assert!(cpu.memory[0x206..0x208] == [0x00, 0x05]);
+        assert!(cpu.v[0] == 5);
+    }
+    #[test]
+    fn cache_platform_change() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x00, 0xff, 0x12, 0x00]).unwrap();
+        let _ = cpu.step();
+        let _ = cpu.step();
+        assert!(!cpu.display.is_hires());
+        cpu.set_platform(Platform::SuperChip);
+        let _ = cpu.step();
+        assert!(cpu.display.is_hires());
+    }
+
+    // QUIRKS
+
+    #[test]
+    fn op_8xy1_logic_reset_vf() {
+        let mut cpu = Cpu::with_quirks(Quirks { logic_reset_vf: true, ..Quirks::default() });
+        cpu.v[4] = 0b00010000;
+        cpu.v[2] = 0b00001000;
+        cpu.v[0xF] = 0x01;
+        cpu.pc = 0x200;
+        cpu.memory[0x200] = 0x84;
+        cpu.memory[0x201] = 0x21;
+        let _ = cpu.step();
+        assert!(cpu.v[4] == 0b00011000);
+        assert!(cpu.v[0xF] == 0x00);
+    }
+    #[test]
+    fn op_8xy6_shift_vy() {
+        let mut cpu = Cpu::with_quirks(Quirks { shift_vy: true, ..Quirks::default() });
+        cpu.v[5] = 0b10011100;
+        cpu.v[0xA] = 0b10011001;
+        cpu.pc = 0x200;
+        cpu.memory[0x200] = 0x85;
+        cpu.memory[0x201] = 0xA6;
+        let _ = cpu.step();
+        assert!(cpu.v[5] == 0b01001100);
+        assert!(cpu.v[0xF] == 0x01);
+    }
+    #[test]
+    fn op_bxnn_jump_vx() {
+        let mut cpu = Cpu::with_quirks(Quirks { jump_vx: true, ..Quirks::default() });
+        cpu.pc = 0x200;
+        cpu.v[0] = 0x04;
+        cpu.v[2] = 0x08;
+        cpu.memory[0x200] = 0xb2;
+        cpu.memory[0x201] = 0x10;
+        let _ = cpu.step();
+        assert!(cpu.pc == 0x218);
+    }
+    #[test]
+    fn op_fx55_memory_increment_i() {
+        let mut cpu = Cpu::with_quirks(Quirks { memory_increment_i: true, ..Quirks::default() });
+        cpu.pc = 0x200;
+        cpu.i = 0x0150;
+        cpu.memory[0x200] = 0xf3;
+        cpu.memory[0x201] = 0x55;
+        let _ = cpu.step();
+        assert!(cpu.i == 0x0154);
+    }
+    #[test]
+    fn op_fx65_memory_increment_i() {
+        let mut cpu = Cpu::with_quirks(Quirks { memory_increment_i: true, ..Quirks::default() });
+        cpu.pc = 0x200;
+        cpu.i = 0x0150;
+        cpu.memory[0x200] = 0xf0;
+        cpu.memory[0x201] = 0x65;
+        let _ = cpu.step();
+        assert!(cpu.i == 0x0151);
+    }
+    #[test]
+    fn op_dxyn_display_wait() {
+        let mut cpu = Cpu::with_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        cpu.pc = 0x200;
+        cpu.i = FONT_ADDR;
+        cpu.memory[0x200] = 0xd0;
+        cpu.memory[0x201] = 0x05;
+        let _ = cpu.step();
+        assert!(cpu.pc == 0x200);
+        assert!(!cpu.take_redraw());
+        cpu.decrease_timers();
+        let _ = cpu.step();
+        assert!(cpu.pc == 0x202);
+        assert!(cpu.take_redraw());
+    }
+    #[test]
+    fn presets() {
+        assert!(Preset::ALL.iter().all(|p| !p.name().is_empty()));
+        let cpu = Cpu::with_quirks(Preset::CosmacVip.quirks());
+        assert!(cpu.quirks() == Quirks::VIP);
+    }
+
+    // SCHIP
+
+    #[test]
+    fn op_00ff_00fe() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.load_rom(0x200, &[0x00, 0xff, 0x00, 0xfe]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.get_display_size() == (128, 64));
+        let _ = cpu.step();
+        assert!(cpu.get_display_size() == (64, 32));
+    }
+    #[test]
+    fn op_00ff_ignored_on_chip8() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x00, 0xff]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.get_display_size() == (64, 32));
+        assert!(cpu.pc == 0x202);
+    }
+    #[test]
+    fn op_00cn() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.display.load(&[0xFF; 8]);
+        cpu.load_rom(0x200, &[0x00, 0xc3]).unwrap();
+        let _ = cpu.step();
+        let buffer = cpu.display.get_buffer();
+        assert!(buffer[0] == 0x00);
+        assert!(buffer[3 * 8] == 0xFF);
+        assert!(cpu.take_redraw());
+    }
+    #[test]
+    fn op_00fd() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.load_rom(0x200, &[0x00, 0xfd, 0x60, 0x01]).unwrap();
+        let _ = cpu.step();
+        let _ = cpu.step();
+        assert!(cpu.exited());
+        assert!(cpu.pc == 0x200);
+        assert!(cpu.v[0] == 0x00);
+    }
+    #[test]
+    fn op_dxy0_hires() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        let mut rom = [0; 0x40];
+        let ins = [
+            0x00, 0xff,
+            0xa2, 0x20,
+            0xd0, 0x10,
+            0xd0, 0x10
+        ];
+        rom[0x0..0x8].copy_from_slice(&ins);
+        rom[0x20..0x40].copy_from_slice(&[0xFF; 32]);
+        cpu.load_rom(0x200, &rom).unwrap();
+        for _ in 0..3 {
+            let _ = cpu.step();
+        }
+        assert!(cpu.v[0xF] == 0);
+        assert!(cpu.display.get_buffer()[15 * 16 + 1] == 0xFF);
+        let _ = cpu.step();
+        assert!(cpu.v[0xF] == 16);
+    }
+    #[test]
+    fn op_dxy0_hires_clipped_rows() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        let mut rom = [0; 0x40];
+        let ins = [
+            0x00, 0xff,
+            0xa2, 0x20,
+            0x61, 0x3c,
+            0xd0, 0x10
+        ];
+        rom[0x0..0x8].copy_from_slice(&ins);
+        rom[0x20..0x40].copy_from_slice(&[0xFF; 32]);
+        cpu.load_rom(0x200, &rom).unwrap();
+        for _ in 0..4 {
+            let _ = cpu.step();
+        }
+        assert!(cpu.v[0xF] == 12);
+    }
+    #[test]
+    fn op_fx30() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.v[3] = 0x09;
+        cpu.load_rom(0x200, &[0xf3, 0x30]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.i == BIG_FONT_ADDR + 90);
+        assert!(cpu.memory[cpu.i as usize..cpu.i as usize + 10] == BIG_FONT[90..100]);
+    }
+    #[test]
+    fn op_fx75_fx85() {
+        let mut cpu = Cpu::with_preset(Preset::SuperChip);
+        cpu.v[0] = 0xcc;
+        cpu.v[1] = 0x07;
+        cpu.v[2] = 0xee;
+        cpu.load_rom(0x200, &[0xf1, 0x75, 0x60, 0x00, 0x61, 0x00, 0xf2, 0x85]).unwrap();
+        for _ in 0..4 {
+            let _ = cpu.step();
+        }
+        assert!(cpu.v[0] == 0xcc);
+        assert!(cpu.v[1] == 0x07);
+        assert!(cpu.v[2] == 0x00);
+    }
+
+    // XO-CHIP
+
+    #[test]
+    fn xo_ram_size() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.load_rom(0x200, &[0x1f, 0x00]).unwrap();
+        cpu.memory[0xF000] = 0x60;
+        cpu.memory[0xF001] = 0x12;
+        let _ = cpu.step();
+        assert!(cpu.pc == 0x0F00);
+        cpu.pc = 0xF000;
+        assert!(cpu.step() == Ok(()));
+        assert!(cpu.v[0] == 0x12);
+        assert!(Cpu::new().ram_size() == RAM_SIZE);
+    }
+    #[test]
+    fn op_f000_nnnn() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.load_rom(0x200, &[0xf0, 0x00, 0xbe, 0xef]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.i == 0xBEEF);
+        assert!(cpu.pc == 0x204);
+    }
+    #[test]
+    fn skip_f000_nnnn() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.load_rom(0x200, &[0x30, 0x00, 0xf0, 0x00, 0xbe, 0xef]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.pc == 0x206);
+    }
+    #[test]
+    fn op_5xy2() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.i = 0x300;
+        cpu.v[2] = 0xcc;
+        cpu.v[3] = 0x07;
+        cpu.v[4] = 0xee;
+        cpu.load_rom(0x200, &[0x52, 0x42, 0x54, 0x22]).unwrap();
+        cpu.memory[0x300..0x303].copy_from_slice(&[0; 3]);
+        let _ = cpu.step();
+        assert!(cpu.memory[0x300..0x303] == [0xcc, 0x07, 0xee]);
+        assert!(cpu.i == 0x300);
+        cpu.i = 0x310;
+        let _ = cpu.step();
+        assert!(cpu.memory[0x310..0x313] == [0xee, 0x07, 0xcc]);
+    }
+    #[test]
+    fn op_5xy3() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.i = 0x300;
+        cpu.memory[0x300..0x302].copy_from_slice(&[0xcc, 0x07]);
+        cpu.load_rom(0x200, &[0x53, 0x23]).unwrap();
+        let _ = cpu.step();
+        assert!(cpu.v[3] == 0xcc);
+        assert!(cpu.v[2] == 0x07);
+    }
+    #[test]
+    fn op_fn01_dxyn() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        let mut rom = [0; 0x40];
+        let ins = [
+            0xf3, 0x01,
+            0xa2, 0x20,
+            0xd0, 0x01
+        ];
+        rom[0x0..0x6].copy_from_slice(&ins);
+        rom[0x20] = 0b10101011;
+        rom[0x21] = 0b11110000;
+        cpu.load_rom(0x200, &rom).unwrap();
+        for _ in 0..3 {
+            let _ = cpu.step();
+        }
+        assert!(cpu.get_plane_buffer(0).unwrap()[0] == 0b10101011);
+        assert!(cpu.get_plane_buffer(1).unwrap()[0] == 0b11110000);
+    }
+    #[test]
+    fn op_f002_fx3a() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.i = 0x300;
+        cpu.memory[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+        cpu.v[1] = 0x70;
+        cpu.load_rom(0x200, &[0xf0, 0x02, 0xf1, 0x3a]).unwrap();
+        assert!(cpu.pitch() == DEFAULT_PITCH);
+        let _ = cpu.step();
+        let _ = cpu.step();
+        assert!(cpu.audio_pattern() == &[0xAA; 16]);
+        assert!(cpu.pitch() == 0x70);
+    }
 }
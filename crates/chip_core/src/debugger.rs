@@ -0,0 +1,405 @@
+//! Breakpoints, watchpoints and stepping control on top of `Cpu::step`.
+//!
+//! Breakpoints stop before the instruction at their address is executed,
+//! watchpoints stop right after the instruction that touched the watched
+//! memory or the I register.
+use alloc::vec::Vec;
+
+use crate::{
+    cpu::{Cpu, RunState},
+    disasm::Instruction,
+    errors::ChipError,
+    globals::AUDIO_PATTERN_SIZE
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+/// Register condition of a breakpoint, e.g. `V3 >= 0x10`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub reg: u8,
+    pub cmp: Compare,
+    pub value: u8
+}
+impl Condition {
+    pub fn new(reg: u8, cmp: Compare, value: u8) -> Self {
+        Condition { reg, cmp, value }
+    }
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        let Some(val) = cpu.v.get(self.reg as usize) else { return false };
+        match self.cmp {
+            Compare::Eq => *val == self.value,
+            Compare::Ne => *val != self.value,
+            Compare::Lt => *val < self.value,
+            Compare::Le => *val <= self.value,
+            Compare::Gt => *val > self.value,
+            Compare::Ge => *val >= self.value,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub condition: Option<Condition>
+}
+
+/// Watches `len` bytes of memory starting at `start`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub len: u16,
+    pub read: bool,
+    pub write: bool
+}
+impl Watchpoint {
+    fn hit(&self, range: (u16, u16)) -> Option<u16> {
+        let start = self.start.max(range.0) as u32;
+        let end = (self.start as u32 + self.len as u32).min(range.0 as u32 + range.1 as u32);
+        if start < end { Some(start as u16) } else { None }
+    }
+}
+
+/// Why execution was handed back to the caller
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    /// the requested step has completed
+    Step,
+    /// the step budget has run out
+    StepLimit,
+    /// about to execute the instruction at the address
+    Breakpoint(u16),
+    /// the previous instruction accessed a watched address
+    Watchpoint { addr: u16, access: Access },
+    /// the previous instruction accessed the I register
+    WatchI(Access),
+    /// FX0A blocks until a key is released into the register
+    WaitingForKey(u8),
+    /// the program has terminated with 00FD
+    Exited,
+    Error(ChipError)
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    watch_i: (bool, bool)
+}
+impl Debugger {
+    pub fn new() -> Self {
+        Debugger::default()
+    }
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.push(Breakpoint { addr, condition: None });
+    }
+    /// Breaks at `addr` only when the register condition holds
+    pub fn add_conditional_breakpoint(&mut self, addr: u16, condition: Condition) {
+        self.breakpoints.push(Breakpoint { addr, condition: Some(condition) });
+    }
+    /// Removes all the breakpoints at `addr`
+    pub fn remove_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.retain(|b| b.addr != addr);
+    }
+    pub fn add_watchpoint(&mut self, start: u16, len: u16, read: bool, write: bool) {
+        self.watchpoints.push(Watchpoint { start, len, read, write });
+    }
+    /// Removes all the watchpoints starting at `start`
+    pub fn remove_watchpoint(&mut self, start: u16) {
+        self.watchpoints.retain(|w| w.start != start);
+    }
+    pub fn watch_i(&mut self, read: bool, write: bool) {
+        self.watch_i = (read, write);
+    }
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.watch_i = (false, false);
+    }
+    /// Executes a single instruction
+    pub fn step_into(&self, cpu: &mut Cpu) -> StopReason {
+        self.exec(cpu, true).unwrap_or(StopReason::Step)
+    }
+    /// Like `step_into`, but runs a 2NNN call until it returns
+    pub fn step_over(&self, cpu: &mut Cpu, max_steps: usize) -> StopReason {
+        let Ok(Instruction::Call(_)) = cpu.current_instruction() else { return self.step_into(cpu) };
+        let (ret, sp) = (cpu.pc.wrapping_add(2), cpu.sp);
+        self.run_until(cpu, max_steps, |cpu| cpu.pc == ret && cpu.sp == sp)
+    }
+    /// Runs until the current subroutine returns with its matching 00EE
+    pub fn step_out(&self, cpu: &mut Cpu, max_steps: usize) -> StopReason {
+        let sp = cpu.sp;
+        if sp == 0 { return self.run(cpu, max_steps) }
+        self.run_until(cpu, max_steps, |cpu| cpu.sp < sp)
+    }
+    /// Runs at most `max_steps` instructions
+    pub fn run(&self, cpu: &mut Cpu, max_steps: usize) -> StopReason {
+        self.run_until(cpu, max_steps, |_| false)
+    }
+    fn run_until(&self, cpu: &mut Cpu, max_steps: usize, done: impl Fn(&Cpu) -> bool) -> StopReason {
+        for n in 0..max_steps {
+            // do not stop on the breakpoint we are resuming from
+            if let Some(reason) = self.exec(cpu, n == 0) { return reason }
+            if done(cpu) { return StopReason::Step }
+        }
+        StopReason::StepLimit
+    }
+    fn exec(&self, cpu: &mut Cpu, resume: bool) -> Option<StopReason> {
+        match cpu.state {
+            RunState::Exited => return Some(StopReason::Exited),
+            RunState::WaitingForKey(x) if !cpu.ready() => return Some(StopReason::WaitingForKey(x)),
+            _ => ()
+        }
+        if !resume && self.breakpoints.iter().any(|b| {
+            b.addr == cpu.pc && b.condition.is_none_or(|c| c.holds(cpu))
+        }) {
+            return Some(StopReason::Breakpoint(cpu.pc));
+        }
+        // a pending FX0A only writes its register
+        let access = if cpu.state == RunState::Running { accesses(cpu) } else { Accesses::default() };
+        if let Err(e) = cpu.step() { return Some(StopReason::Error(e)) }
+        if let Some(reason) = self.check_watchpoints(&access) { return Some(reason) }
+        match cpu.state {
+            RunState::Exited => Some(StopReason::Exited),
+            RunState::WaitingForKey(x) => Some(StopReason::WaitingForKey(x)),
+            _ => None
+        }
+    }
+    fn check_watchpoints(&self, access: &Accesses) -> Option<StopReason> {
+        for w in self.watchpoints.iter() {
+            let hits = [(w.read, access.read, Access::Read), (w.write, access.write, Access::Write)];
+            for (enabled, range, kind) in hits {
+                if !enabled { continue }
+                if let Some(addr) = range.and_then(|r| w.hit(r)) {
+                    return Some(StopReason::Watchpoint { addr, access: kind });
+                }
+            }
+        }
+        if self.watch_i.0 && access.i_read { return Some(StopReason::WatchI(Access::Read)) }
+        if self.watch_i.1 && access.i_write { return Some(StopReason::WatchI(Access::Write)) }
+        None
+    }
+}
+
+/// Memory ranges as (start, len) and I register usage of an instruction
+#[derive(Default)]
+struct Accesses {
+    read: Option<(u16, u16)>,
+    write: Option<(u16, u16)>,
+    i_read: bool,
+    i_write: bool
+}
+
+/// Decodes what the instruction at PC is going to access
+fn accesses(cpu: &Cpu) -> Accesses {
+    let mut acc = Accesses::default();
+    let Ok(inst) = cpu.current_instruction() else { return acc };
+    let i = cpu.i;
+    let increment = cpu.quirks.memory_increment_i;
+    match inst {
+        Instruction::SaveRange(x, y) => {
+            acc.write = Some((i, x.abs_diff(y) as u16 + 1));
+            acc.i_read = true;
+        },
+        Instruction::LoadRange(x, y) => {
+            acc.read = Some((i, x.abs_diff(y) as u16 + 1));
+            acc.i_read = true;
+        },
+        Instruction::SetI(_) | Instruction::LongI | Instruction::Font(_) | Instruction::BigFont(_) => {
+            acc.i_write = true;
+        },
+        Instruction::Draw(_, _, n) => {
+            // a stalled draw does not touch anything
+            if cpu.quirks.display_wait && !cpu.vblank { return acc }
+            let len = if n == 0 && cpu.extended() { 32 } else { n as u16 };
+            acc.read = Some((i, len * cpu.display.selected().count_ones() as u16));
+            acc.i_read = true;
+        },
+        Instruction::Audio => {
+            acc.read = Some((i, AUDIO_PATTERN_SIZE as u16));
+            acc.i_read = true;
+        },
+        Instruction::AddI(_) => {
+            acc.i_read = true;
+            acc.i_write = true;
+        },
+        Instruction::Bcd(_) => {
+            acc.write = Some((i, 3));
+            acc.i_read = true;
+        },
+        Instruction::Store(x) => {
+            acc.write = Some((i, x as u16 + 1));
+            acc.i_read = true;
+            acc.i_write = increment;
+        },
+        Instruction::Load(x) => {
+            acc.read = Some((i, x as u16 + 1));
+            acc.i_read = true;
+            acc.i_write = increment;
+        },
+        _ => ()
+    }
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::ErrorKind;
+
+    // main loop calling a subroutine that stores V0 as BCD
+    const ROM: [u8; 16] = [
+        0xa3, 0x00, // 200: I = 0x300
+        0x22, 0x08, // 202: call 0x208
+        0x70, 0x01, // 204: V0 += 1
+        0x12, 0x02, // 206: jump 0x202
+        0xf0, 0x33, // 208: BCD V0
+        0x61, 0x05, // 20A: V1 = 5
+        0x00, 0xee, // 20C: return
+        0x00, 0x00
+    ];
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn breakpoint() {
+        let mut cpu = cpu();
+        let mut dbg = Debugger::new();
+        dbg.add_breakpoint(0x204);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::Breakpoint(0x204));
+        assert!(cpu.pc == 0x204);
+        // resuming does not stop on the same breakpoint
+        assert!(dbg.run(&mut cpu, 100) == StopReason::Breakpoint(0x204));
+        assert!(cpu.v[0] == 1);
+        dbg.remove_breakpoint(0x204);
+        assert!(dbg.run(&mut cpu, 10) == StopReason::StepLimit);
+    }
+    #[test]
+    fn conditional_breakpoint() {
+        let mut cpu = cpu();
+        let mut dbg = Debugger::new();
+        dbg.add_conditional_breakpoint(0x206, Condition::new(0, Compare::Ge, 3));
+        assert!(dbg.run(&mut cpu, 100) == StopReason::Breakpoint(0x206));
+        assert!(cpu.v[0] == 3);
+    }
+    #[test]
+    fn memory_watchpoint() {
+        let mut cpu = cpu();
+        let mut dbg = Debugger::new();
+        dbg.add_watchpoint(0x302, 1, false, true);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::Watchpoint { addr: 0x302, access: Access::Write });
+        assert!(cpu.pc == 0x20A);
+
+        dbg.clear();
+        dbg.add_watchpoint(0x2FF, 2, true, false);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::StepLimit);
+    }
+    #[test]
+    fn read_watchpoint() {
+        let mut cpu = cpu();
+        cpu.load_rom(0x210, &[0xa3, 0x00, 0xf1, 0x65]).unwrap();
+        let mut dbg = Debugger::new();
+        dbg.add_watchpoint(0x301, 4, true, false);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::Watchpoint { addr: 0x301, access: Access::Read });
+    }
+    #[test]
+    fn watch_i() {
+        let mut cpu = cpu();
+        let mut dbg = Debugger::new();
+        dbg.watch_i(false, true);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::WatchI(Access::Write));
+        assert!(cpu.pc == 0x202);
+        dbg.watch_i(true, false);
+        assert!(dbg.run(&mut cpu, 100) == StopReason::WatchI(Access::Read));
+        assert!(cpu.pc == 0x20A);
+    }
+    #[test]
+    fn step_over_and_out() {
+        let mut cpu = cpu();
+        let dbg = Debugger::new();
+        assert!(dbg.step_into(&mut cpu) == StopReason::Step);
+        assert!(dbg.step_over(&mut cpu, 100) == StopReason::Step);
+        assert!(cpu.pc == 0x204);
+
+        for _ in 0..3 {
+            let _ = dbg.step_into(&mut cpu);
+        }
+        assert!(cpu.pc == 0x208);
+        assert!(dbg.step_out(&mut cpu, 100) == StopReason::Step);
+        assert!(cpu.pc == 0x204);
+        assert!(cpu.sp == 0);
+        assert!(cpu.memory[0x302] == 1);
+    }
+    #[test]
+    fn step_over_wraps() {
+        // a call at the very end of XO-CHIP memory returns to 0
+        let mut cpu = Cpu::with_preset(crate::quirks::Preset::XoChip);
+        cpu.load_rom(0x200, &ROM).unwrap();
+        cpu.load_rom(0xFFFE, &[0x22, 0x08]).unwrap();
+        cpu.pc = 0xFFFE;
+        let dbg = Debugger::new();
+        assert!(dbg.step_over(&mut cpu, 100) == StopReason::Step);
+        assert!(cpu.pc == 0 && cpu.sp == 0);
+    }
+    #[test]
+    fn step_over_stops_on_breakpoint() {
+        let mut cpu = cpu();
+        let mut dbg = Debugger::new();
+        dbg.add_breakpoint(0x20A);
+        let _ = dbg.step_into(&mut cpu);
+        assert!(dbg.step_over(&mut cpu, 100) == StopReason::Breakpoint(0x20A));
+    }
+    #[test]
+    fn error_and_exit() {
+        let mut cpu = cpu();
+        cpu.pc = 0x20C;
+        let dbg = Debugger::new();
+        let error = ChipError { kind: ErrorKind::StackUnderflow, pc: 0x20C, opcode: Some(0x00EE) };
+        assert!(dbg.step_into(&mut cpu) == StopReason::Error(error));
+
+        let mut cpu = Cpu::with_preset(crate::quirks::Preset::SuperChip);
+        cpu.load_rom(0x200, &[0x00, 0xfd]).unwrap();
+        assert!(dbg.run(&mut cpu, 10) == StopReason::Exited);
+        assert!(dbg.step_into(&mut cpu) == StopReason::Exited);
+    }
+    #[test]
+    fn waiting_for_key() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0xf2, 0x0a, 0x60, 0x01]).unwrap();
+        let dbg = Debugger::new();
+        assert!(dbg.run(&mut cpu, 100) == StopReason::WaitingForKey(2));
+        assert!(dbg.run(&mut cpu, 100) == StopReason::WaitingForKey(2));
+        cpu.key_down(9).unwrap();
+        cpu.key_up(9).unwrap();
+        // the press, then the release completing FX0A
+        assert!(dbg.step_into(&mut cpu) == StopReason::WaitingForKey(2));
+        assert!(dbg.step_into(&mut cpu) == StopReason::Step);
+        assert!(cpu.v[2] == 9);
+        assert!(dbg.step_into(&mut cpu) == StopReason::Step);
+        assert!(cpu.v[0] == 1);
+    }
+}
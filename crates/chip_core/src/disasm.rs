@@ -0,0 +1,536 @@
+//! Instruction decoding and disassembly.
+//!
+//! `Instruction::decode` is the single source of truth for opcode decoding,
+//! `Cpu::step` executes the decoded instructions.
+use core::fmt;
+
+use crate::quirks::Platform;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN machine subroutine, ignored
+    Sys(u16),
+    /// 00CN
+    ScrollDown(u8),
+    /// 00DN
+    ScrollUp(u8),
+    /// 00E0
+    Cls,
+    /// 00EE
+    Ret,
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    Lores,
+    /// 00FF
+    Hires,
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipEqImm(u8, u8),
+    /// 4XNN
+    SkipNeImm(u8, u8),
+    /// 5XY0
+    SkipEq(u8, u8),
+    /// 5XY2
+    SaveRange(u8, u8),
+    /// 5XY3
+    LoadRange(u8, u8),
+    /// 6XNN
+    SetImm(u8, u8),
+    /// 7XNN
+    AddImm(u8, u8),
+    /// 8XY0
+    Set(u8, u8),
+    /// 8XY1
+    Or(u8, u8),
+    /// 8XY2
+    And(u8, u8),
+    /// 8XY3
+    Xor(u8, u8),
+    /// 8XY4
+    Add(u8, u8),
+    /// 8XY5
+    Sub(u8, u8),
+    /// 8XY6
+    Shr(u8, u8),
+    /// 8XY7
+    SubN(u8, u8),
+    /// 8XYE
+    Shl(u8, u8),
+    /// 9XY0
+    SkipNe(u8, u8),
+    /// ANNN
+    SetI(u16),
+    /// BNNN
+    JumpOffset(u16),
+    /// CXNN
+    Random(u8, u8),
+    /// DXYN
+    Draw(u8, u8, u8),
+    /// EX9E
+    SkipKey(u8),
+    /// EXA1
+    SkipNotKey(u8),
+    /// F000 NNNN, the address is stored in the following word
+    LongI,
+    /// FN01
+    Planes(u8),
+    /// F002
+    Audio,
+    /// FX07
+    GetDelay(u8),
+    /// FX0A
+    WaitKey(u8),
+    /// FX15
+    SetDelay(u8),
+    /// FX18
+    SetSound(u8),
+    /// FX1E
+    AddI(u8),
+    /// FX29
+    Font(u8),
+    /// FX30
+    BigFont(u8),
+    /// FX33
+    Bcd(u8),
+    /// FX3A
+    Pitch(u8),
+    /// FX55
+    Store(u8),
+    /// FX65
+    Load(u8),
+    /// FX75
+    SaveFlags(u8),
+    /// FX85
+    LoadFlags(u8),
+    Unknown(u16)
+}
+impl Instruction {
+    /// Decodes a word, instructions of extensions missing on the platform are Unknown
+    pub fn decode(word: u16, platform: Platform) -> Instruction {
+        use Instruction::*;
+        let extended = platform != Platform::Chip8;
+        let xo = platform == Platform::XoChip;
+        let x = (word >> 8 & 0xF) as u8;
+        let y = (word >> 4 & 0xF) as u8;
+        let n = (word & 0xF) as u8;
+        let nn = (word & 0xFF) as u8;
+        let nnn = word & 0xFFF;
+        match (word >> 12, x, y, n) {
+            (0, 0, 0xC, n) if extended => ScrollDown(n),
+            (0, 0, 0xD, n) if xo => ScrollUp(n),
+            (0, 0, 0xE, 0) => Cls,
+            (0, 0, 0xE, 0xE) => Ret,
+            (0, 0, 0xF, 0xB) if extended => ScrollRight,
+            (0, 0, 0xF, 0xC) if extended => ScrollLeft,
+            (0, 0, 0xF, 0xD) if extended => Exit,
+            (0, 0, 0xF, 0xE) if extended => Lores,
+            (0, 0, 0xF, 0xF) if extended => Hires,
+            (0, _, _, _) => Sys(nnn),
+            (1, _, _, _) => Jump(nnn),
+            (2, _, _, _) => Call(nnn),
+            (3, x, _, _) => SkipEqImm(x, nn),
+            (4, x, _, _) => SkipNeImm(x, nn),
+            (5, x, y, 0) => SkipEq(x, y),
+            (5, x, y, 2) if xo => SaveRange(x, y),
+            (5, x, y, 3) if xo => LoadRange(x, y),
+            (6, x, _, _) => SetImm(x, nn),
+            (7, x, _, _) => AddImm(x, nn),
+            (8, x, y, 0) => Set(x, y),
+            (8, x, y, 1) => Or(x, y),
+            (8, x, y, 2) => And(x, y),
+            (8, x, y, 3) => Xor(x, y),
+            (8, x, y, 4) => Add(x, y),
+            (8, x, y, 5) => Sub(x, y),
+            (8, x, y, 6) => Shr(x, y),
+            (8, x, y, 7) => SubN(x, y),
+            (8, x, y, 0xE) => Shl(x, y),
+            (9, x, y, 0) => SkipNe(x, y),
+            (0xA, _, _, _) => SetI(nnn),
+            (0xB, _, _, _) => JumpOffset(nnn),
+            (0xC, x, _, _) => Random(x, nn),
+            (0xD, x, y, n) => Draw(x, y, n),
+            (0xE, x, 9, 0xE) => SkipKey(x),
+            (0xE, x, 0xA, 1) => SkipNotKey(x),
+            (0xF, 0, 0, 0) if xo => LongI,
+            (0xF, n, 0, 1) if xo => Planes(n),
+            (0xF, 0, 0, 2) if xo => Audio,
+            (0xF, x, 0, 7) => GetDelay(x),
+            (0xF, x, 0, 0xA) => WaitKey(x),
+            (0xF, x, 1, 5) => SetDelay(x),
+            (0xF, x, 1, 8) => SetSound(x),
+            (0xF, x, 1, 0xE) => AddI(x),
+            (0xF, x, 2, 9) => Font(x),
+            (0xF, x, 3, 0) if extended => BigFont(x),
+            (0xF, x, 3, 3) => Bcd(x),
+            (0xF, x, 3, 0xA) if xo => Pitch(x),
+            (0xF, x, 5, 5) => Store(x),
+            (0xF, x, 6, 5) => Load(x),
+            (0xF, x, 7, 5) if extended => SaveFlags(x),
+            (0xF, x, 8, 5) if extended => LoadFlags(x),
+            _ => Unknown(word)
+        }
+    }
+    /// Inverse of `decode`, operands are masked to their field width
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+        let xy = |op: u16, x: u8, y: u8, n: u16| op << 12 | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xnn = |op: u16, x: u8, nn: u8| op << 12 | (x as u16 & 0xF) << 8 | nn as u16;
+        let fx = |x: u8, nn: u16| 0xF000 | (x as u16 & 0xF) << 8 | nn;
+        match *self {
+            Sys(nnn) => nnn & 0xFFF,
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jump(nnn) => 0x1000 | (nnn & 0xFFF),
+            Call(nnn) => 0x2000 | (nnn & 0xFFF),
+            SkipEqImm(x, nn) => xnn(3, x, nn),
+            SkipNeImm(x, nn) => xnn(4, x, nn),
+            SkipEq(x, y) => xy(5, x, y, 0),
+            SaveRange(x, y) => xy(5, x, y, 2),
+            LoadRange(x, y) => xy(5, x, y, 3),
+            SetImm(x, nn) => xnn(6, x, nn),
+            AddImm(x, nn) => xnn(7, x, nn),
+            Set(x, y) => xy(8, x, y, 0),
+            Or(x, y) => xy(8, x, y, 1),
+            And(x, y) => xy(8, x, y, 2),
+            Xor(x, y) => xy(8, x, y, 3),
+            Add(x, y) => xy(8, x, y, 4),
+            Sub(x, y) => xy(8, x, y, 5),
+            Shr(x, y) => xy(8, x, y, 6),
+            SubN(x, y) => xy(8, x, y, 7),
+            Shl(x, y) => xy(8, x, y, 0xE),
+            SkipNe(x, y) => xy(9, x, y, 0),
+            SetI(nnn) => 0xA000 | (nnn & 0xFFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0xFFF),
+            Random(x, nn) => xnn(0xC, x, nn),
+            Draw(x, y, n) => xy(0xD, x, y, n as u16 & 0xF),
+            SkipKey(x) => xnn(0xE, x, 0x9E),
+            SkipNotKey(x) => xnn(0xE, x, 0xA1),
+            LongI => 0xF000,
+            Planes(n) => fx(n, 0x01),
+            Audio => 0xF002,
+            GetDelay(x) => fx(x, 0x07),
+            WaitKey(x) => fx(x, 0x0A),
+            SetDelay(x) => fx(x, 0x15),
+            SetSound(x) => fx(x, 0x18),
+            AddI(x) => fx(x, 0x1E),
+            Font(x) => fx(x, 0x29),
+            BigFont(x) => fx(x, 0x30),
+            Bcd(x) => fx(x, 0x33),
+            Pitch(x) => fx(x, 0x3A),
+            Store(x) => fx(x, 0x55),
+            Load(x) => fx(x, 0x65),
+            SaveFlags(x) => fx(x, 0x75),
+            LoadFlags(x) => fx(x, 0x85),
+            Unknown(word) => word,
+        }
+    }
+    /// Length in bytes, including the operand of `LongI`
+    pub fn size(&self) -> usize {
+        if *self == Instruction::LongI { 4 } else { 2 }
+    }
+    /// Classic mnemonics, e.g. `LD V0, 0x12`
+    pub fn classic(&self) -> Classic {
+        Classic(*self)
+    }
+    /// Octo syntax, e.g. `v0 := 0x12`
+    pub fn octo(&self) -> Octo {
+        Octo(*self)
+    }
+}
+
+pub struct Classic(Instruction);
+impl fmt::Display for Classic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+        match self.0 {
+            Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            SkipEqImm(x, nn) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            SkipNeImm(x, nn) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            SkipEq(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange(x, y) => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            LoadRange(x, y) => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            SetImm(x, nn) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            AddImm(x, nn) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Set(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNe(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            SetI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            JumpOffset(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Random(x, nn) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey(x) => write!(f, "SKP V{:X}", x),
+            SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            LongI => write!(f, "LD I, LONG"),
+            Planes(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            GetDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Font(x) => write!(f, "LD F, V{:X}", x),
+            BigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Load(x) => write!(f, "LD V{:X}, [I]", x),
+            SaveFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Unknown(word) => write!(f, "DW {:#06X}", word),
+        }
+    }
+}
+
+pub struct Octo(Instruction);
+impl fmt::Display for Octo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+        match self.0 {
+            Sys(nnn) => write!(f, "{:#04X} {:#04X}", nnn >> 8, nnn & 0xFF),
+            ScrollDown(n) => write!(f, "scroll-down {}", n),
+            ScrollUp(n) => write!(f, "scroll-up {}", n),
+            Cls => write!(f, "clear"),
+            Ret => write!(f, "return"),
+            ScrollRight => write!(f, "scroll-right"),
+            ScrollLeft => write!(f, "scroll-left"),
+            Exit => write!(f, "exit"),
+            Lores => write!(f, "lores"),
+            Hires => write!(f, "hires"),
+            Jump(nnn) => write!(f, "jump {:#05X}", nnn),
+            Call(nnn) => write!(f, ":call {:#05X}", nnn),
+            // the skips read as the condition under which the next line runs
+            SkipEqImm(x, nn) => write!(f, "if v{:x} != {:#04X} then", x, nn),
+            SkipNeImm(x, nn) => write!(f, "if v{:x} == {:#04X} then", x, nn),
+            SkipEq(x, y) => write!(f, "if v{:x} != v{:x} then", x, y),
+            SaveRange(x, y) => write!(f, "save v{:x} - v{:x}", x, y),
+            LoadRange(x, y) => write!(f, "load v{:x} - v{:x}", x, y),
+            SetImm(x, nn) => write!(f, "v{:x} := {:#04X}", x, nn),
+            AddImm(x, nn) => write!(f, "v{:x} += {:#04X}", x, nn),
+            Set(x, y) => write!(f, "v{:x} := v{:x}", x, y),
+            Or(x, y) => write!(f, "v{:x} |= v{:x}", x, y),
+            And(x, y) => write!(f, "v{:x} &= v{:x}", x, y),
+            Xor(x, y) => write!(f, "v{:x} ^= v{:x}", x, y),
+            Add(x, y) => write!(f, "v{:x} += v{:x}", x, y),
+            Sub(x, y) => write!(f, "v{:x} -= v{:x}", x, y),
+            Shr(x, y) => write!(f, "v{:x} >>= v{:x}", x, y),
+            SubN(x, y) => write!(f, "v{:x} =- v{:x}", x, y),
+            Shl(x, y) => write!(f, "v{:x} <<= v{:x}", x, y),
+            SkipNe(x, y) => write!(f, "if v{:x} == v{:x} then", x, y),
+            SetI(nnn) => write!(f, "i := {:#05X}", nnn),
+            JumpOffset(nnn) => write!(f, "jump0 {:#05X}", nnn),
+            Random(x, nn) => write!(f, "v{:x} := random {:#04X}", x, nn),
+            Draw(x, y, n) => write!(f, "sprite v{:x} v{:x} {}", x, y, n),
+            SkipKey(x) => write!(f, "if v{:x} -key then", x),
+            SkipNotKey(x) => write!(f, "if v{:x} key then", x),
+            LongI => write!(f, "i := long"),
+            Planes(n) => write!(f, "plane {}", n),
+            Audio => write!(f, "audio"),
+            GetDelay(x) => write!(f, "v{:x} := delay", x),
+            WaitKey(x) => write!(f, "v{:x} := key", x),
+            SetDelay(x) => write!(f, "delay := v{:x}", x),
+            SetSound(x) => write!(f, "buzzer := v{:x}", x),
+            AddI(x) => write!(f, "i += v{:x}", x),
+            Font(x) => write!(f, "i := hex v{:x}", x),
+            BigFont(x) => write!(f, "i := bighex v{:x}", x),
+            Bcd(x) => write!(f, "bcd v{:x}", x),
+            Pitch(x) => write!(f, "pitch := v{:x}", x),
+            Store(x) => write!(f, "save v{:x}", x),
+            Load(x) => write!(f, "load v{:x}", x),
+            SaveFlags(x) => write!(f, "saveflags v{:x}", x),
+            LoadFlags(x) => write!(f, "loadflags v{:x}", x),
+            Unknown(word) => write!(f, "{:#04X} {:#04X}", word >> 8, word & 0xFF),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    Classic,
+    Octo
+}
+
+/// A single line of a listing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    /// raw bytes, `len` of them are used
+    pub bytes: [u8; 4],
+    pub len: usize,
+    /// None for a trailing odd byte
+    pub inst: Option<Instruction>
+}
+
+/// Walks a ROM loaded at `addr` two bytes at a time
+pub fn lines(rom: &[u8], addr: u16, platform: Platform) -> impl Iterator<Item=Line> + '_ {
+    let mut pos = 0;
+    core::iter::from_fn(move || {
+        let rest = rom.get(pos..).filter(|r| !r.is_empty())?;
+        let mut bytes = [0; 4];
+        let (inst, len) = if rest.len() < 2 {
+            (None, 1)
+        } else {
+            let inst = Instruction::decode((rest[0] as u16) << 8 | rest[1] as u16, platform);
+            let len = if rest.len() >= inst.size() { inst.size() } else { 2 };
+            (Some(inst), len)
+        };
+        bytes[..len].copy_from_slice(&rest[..len]);
+        let line = Line { addr: addr.wrapping_add(pos as u16), bytes, len, inst };
+        pos += len;
+        Some(line)
+    })
+}
+
+/// Writes an annotated listing: jump and call targets get labels,
+/// every line shows its address and raw bytes
+pub fn write_listing(
+    out: &mut impl fmt::Write,
+    rom: &[u8],
+    addr: u16,
+    platform: Platform,
+    syntax: Syntax
+) -> fmt::Result {
+    // bitsets of the 12-bit call and jump targets
+    let mut calls = [0u64; 64];
+    let mut jumps = [0u64; 64];
+    for inst in lines(rom, addr, platform).filter_map(|l| l.inst) {
+        match inst {
+            Instruction::Call(nnn) => calls[nnn as usize / 64] |= 1 << (nnn % 64),
+            Instruction::Jump(nnn) => jumps[nnn as usize / 64] |= 1 << (nnn % 64),
+            _ => ()
+        }
+    }
+    let marked = |set: &[u64; 64], a: u16| a < 0x1000 && set[a as usize / 64] & 1 << (a % 64) != 0;
+    for line in lines(rom, addr, platform) {
+        if marked(&calls, line.addr) {
+            writeln!(out, "sub_{:03X}:", line.addr)?;
+        } else if marked(&jumps, line.addr) {
+            writeln!(out, "label_{:03X}:", line.addr)?;
+        }
+        write!(out, "{:04X}  ", line.addr)?;
+        for i in 0..4 {
+            if i < line.len { write!(out, "{:02X} ", line.bytes[i])? } else { write!(out, "   ")? }
+        }
+        let Some(inst) = line.inst else {
+            writeln!(out, " {:#04X}", line.bytes[0])?;
+            continue;
+        };
+        match syntax {
+            Syntax::Classic => write!(out, " {}", inst.classic())?,
+            Syntax::Octo => write!(out, " {}", inst.octo())?,
+        }
+        if inst == Instruction::LongI && line.len == 4 {
+            write!(out, " {:#06X}", (line.bytes[2] as u16) << 8 | line.bytes[3] as u16)?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Annotated listing of a whole ROM
+#[cfg(feature = "alloc")]
+pub fn listing(rom: &[u8], addr: u16, platform: Platform, syntax: Syntax) -> alloc::string::String {
+    let mut out = alloc::string::String::new();
+    let _ = write_listing(&mut out, rom, addr, platform, syntax);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Instruction::*;
+
+    #[test]
+    fn decode_platforms() {
+        assert!(Instruction::decode(0x00FF, Platform::Chip8) == Sys(0x0FF));
+        assert!(Instruction::decode(0x00FF, Platform::SuperChip) == Hires);
+        assert!(Instruction::decode(0x00D2, Platform::SuperChip) == Sys(0x0D2));
+        assert!(Instruction::decode(0x00D2, Platform::XoChip) == ScrollUp(2));
+        assert!(Instruction::decode(0x5122, Platform::Chip8) == Unknown(0x5122));
+        assert!(Instruction::decode(0x5122, Platform::XoChip) == SaveRange(1, 2));
+        assert!(Instruction::decode(0xF000, Platform::XoChip) == LongI);
+        assert!(Instruction::decode(0xF301, Platform::XoChip) == Planes(3));
+    }
+    #[test]
+    fn encode_roundtrip() {
+        for word in 0..=u16::MAX {
+            let inst = Instruction::decode(word, Platform::XoChip);
+            assert!(inst.encode() == word);
+        }
+    }
+    #[test]
+    fn decode_all() {
+        assert!(Instruction::decode(0x2A5F, Platform::Chip8) == Call(0xA5F));
+        assert!(Instruction::decode(0x8AB6, Platform::Chip8) == Shr(0xA, 0xB));
+        assert!(Instruction::decode(0xD125, Platform::Chip8) == Draw(1, 2, 5));
+        assert!(Instruction::decode(0xE39E, Platform::Chip8) == SkipKey(3));
+        assert!(Instruction::decode(0xF465, Platform::Chip8) == Load(4));
+        assert!(Instruction::decode(0xE000, Platform::Chip8) == Unknown(0xE000));
+        assert!(Instruction::decode(0x8008, Platform::XoChip) == Unknown(0x8008));
+    }
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn mnemonics() {
+        use alloc::format;
+        let both = |i: Instruction| format!("{}|{}", i.classic(), i.octo());
+        assert!(both(SetImm(3, 0x1F)) == "LD V3, 0x1F|v3 := 0x1F");
+        assert!(both(SkipEqImm(0xA, 5)) == "SE VA, 0x05|if va != 0x05 then");
+        assert!(both(Call(0x208)) == "CALL 0x208|:call 0x208");
+        assert!(both(Unknown(0xE000)) == "DW 0xE000|0xE0 0x00");
+    }
+    #[test]
+    fn lines_long_i() {
+        let rom = [0xF0, 0x00, 0x12, 0x34, 0x00, 0xE0, 0xAB];
+        let lines: [Line; 3] = {
+            let mut it = lines(&rom, 0x200, Platform::XoChip);
+            [it.next().unwrap(), it.next().unwrap(), it.next().unwrap()]
+        };
+        assert!(lines[0].inst == Some(LongI) && lines[0].len == 4);
+        assert!(lines[1].addr == 0x204 && lines[1].inst == Some(Cls));
+        assert!(lines[2].addr == 0x206 && lines[2].inst.is_none());
+    }
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn listing_labels() {
+        let rom = [0x22, 0x04, 0x12, 0x00, 0x00, 0xEE];
+        let text = listing(&rom, 0x200, Platform::Chip8, Syntax::Octo);
+        let expected = "label_200:\n\
+            0200  22 04        :call 0x204\n\
+            0202  12 00        jump 0x200\n\
+            sub_204:\n\
+            0204  00 EE        return\n";
+        assert!(text == expected);
+    }
+}
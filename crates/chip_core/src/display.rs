@@ -1,57 +1,191 @@
-use crate::globals::{SCREEN_WIDTH, SCREEN_HEIGHT, SCREEN_BUFFER_SIZE};
+use crate::globals::{
+    SCREEN_WIDTH, SCREEN_HEIGHT, HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_BUFFER_SIZE,
+    PLANE_COUNT
+};
 
 pub struct Display {
     // TODO do not use u8s
-    buffer: [u8; SCREEN_BUFFER_SIZE]
+    pub(crate) buffers: [[u8; HIRES_SCREEN_BUFFER_SIZE]; PLANE_COUNT],
+    pub(crate) hires: bool,
+    // bitmask of the planes affected by drawing, clearing and scrolling
+    pub(crate) selected: u8
 }
 impl Display {
     pub fn new() -> Self {
         Display {
-            buffer: [0; SCREEN_BUFFER_SIZE]
+            buffers: [[0; HIRES_SCREEN_BUFFER_SIZE]; PLANE_COUNT],
+            hires: false,
+            selected: 1
         }
     }
+    /// clears the selected planes
     pub fn clear(&mut self) {
-        self.buffer = [0x0; SCREEN_BUFFER_SIZE];
+        for plane in self.selected_planes() {
+            self.buffers[plane] = [0x0; HIRES_SCREEN_BUFFER_SIZE];
+        }
     }
-    pub fn load(&mut self, data: &[u8; SCREEN_BUFFER_SIZE]) {
-        self.buffer.copy_from_slice(data);
+    #[cfg(test)]
+    pub fn load(&mut self, data: &[u8]) {
+        self.buffers[0][..data.len()].copy_from_slice(data);
     }
-    pub fn get_buffer(&self) -> &[u8; SCREEN_BUFFER_SIZE] {
-        &self.buffer
+    /// returns the first plane
+    pub fn get_buffer(&self) -> &[u8] {
+        &self.buffers[0][..self.size()]
     }
-    /// returns a collision flag
-    pub fn blit_sprite(&mut self, mut x: usize, y: usize, data: &[u8], lines: usize) -> u8 {
-        x = x % SCREEN_WIDTH;
-        let mut flag = 0;
-        for i in 0..lines {
-            flag |= self.blit_byte(x, y + i, data[i]);
+    pub fn get_plane_buffer(&self, plane: usize) -> Option<&[u8]> {
+        let size = self.size();
+        self.buffers.get(plane).map(|b| &b[..size])
+    }
+    pub fn selected(&self) -> u8 {
+        self.selected
+    }
+    pub fn select_planes(&mut self, mask: u8) {
+        self.selected = mask & ((1 << PLANE_COUNT) - 1);
+    }
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+    /// switches the resolution and clears all the planes
+    pub fn set_hires(&mut self, val: bool) {
+        self.hires = val;
+        self.buffers = [[0x0; HIRES_SCREEN_BUFFER_SIZE]; PLANE_COUNT];
+    }
+    pub fn width(&self) -> usize {
+        if self.hires { HIRES_SCREEN_WIDTH } else { SCREEN_WIDTH }
+    }
+    pub fn height(&self) -> usize {
+        if self.hires { HIRES_SCREEN_HEIGHT } else { SCREEN_HEIGHT }
+    }
+    pub(crate) fn size(&self) -> usize {
+        self.width() * self.height() / 8
+    }
+    /// draws on every selected plane, consuming `lines` bytes of data per plane
+    /// returns the number of rows with a collision
+    pub fn blit_sprite(&mut self, x: usize, y: usize, data: &[u8], lines: usize, wrap: bool) -> u8 {
+        self.blit_planes(x, y, data, lines, 1, wrap)
+    }
+    /// blits a 16x16 sprite (two bytes per row) on every selected plane
+    /// returns the number of rows with a collision
+    pub fn blit_sprite_16(&mut self, x: usize, y: usize, data: &[u8], wrap: bool) -> u8 {
+        self.blit_planes(x, y, data, 32, 2, wrap)
+    }
+    pub fn scroll_down(&mut self, n: usize) {
+        let offset = (n * self.width() / 8).min(self.size());
+        let size = self.size();
+        for plane in self.selected_planes() {
+            let buffer = &mut self.buffers[plane];
+            buffer.copy_within(0..size - offset, offset);
+            buffer[..offset].fill(0);
+        }
+    }
+    pub fn scroll_up(&mut self, n: usize) {
+        let offset = (n * self.width() / 8).min(self.size());
+        let size = self.size();
+        for plane in self.selected_planes() {
+            let buffer = &mut self.buffers[plane];
+            buffer.copy_within(offset..size, 0);
+            buffer[size - offset..size].fill(0);
+        }
+    }
+    pub fn scroll_right(&mut self) {
+        let cols = self.width() / 8;
+        let size = self.size();
+        for plane in self.selected_planes() {
+            for row in self.buffers[plane][..size].chunks_mut(cols) {
+                for b in (0..cols).rev() {
+                    let carry = if b > 0 { row[b - 1] << 4 } else { 0 };
+                    row[b] = row[b] >> 4 | carry;
+                }
+            }
+        }
+    }
+    pub fn scroll_left(&mut self) {
+        let cols = self.width() / 8;
+        let size = self.size();
+        for plane in self.selected_planes() {
+            for row in self.buffers[plane][..size].chunks_mut(cols) {
+                for b in 0..cols {
+                    let carry = if b + 1 < cols { row[b + 1] >> 4 } else { 0 };
+                    row[b] = row[b] << 4 | carry;
+                }
+            }
         }
-        flag
+    }
+    fn selected_planes(&self) -> impl Iterator<Item=usize> {
+        let selected = self.selected;
+        (0..PLANE_COUNT).filter(move |p| selected & (1 << p) != 0)
+    }
+    fn blit_planes(
+        &mut self,
+        x: usize,
+        y: usize,
+        data: &[u8],
+        len: usize,
+        row_bytes: usize,
+        wrap: bool
+    ) -> u8 {
+        // bitmask of the colliding rows
+        let mut rows = 0u32;
+        for (k, plane) in self.selected_planes().enumerate() {
+            let Some(data) = data.get(k * len..(k + 1) * len) else { break };
+            rows |= self.blit_plane(plane, x, y, data, row_bytes, wrap);
+        }
+        rows.count_ones() as u8
+    }
+    fn blit_plane(
+        &mut self,
+        plane: usize,
+        x: usize,
+        y: usize,
+        data: &[u8],
+        row_bytes: usize,
+        wrap: bool
+    ) -> u32 {
+        let x = x % self.width();
+        let y = y % self.height();
+        let mut rows = 0;
+        for (i, line) in data.chunks(row_bytes).enumerate() {
+            let Some(row) = self.sprite_row(y + i, wrap) else { break };
+            let mut flag = 0;
+            for (b, byte) in line.iter().enumerate() {
+                let bx = x + 8 * b;
+                if bx >= self.width() && !wrap { break }
+                flag |= self.blit_byte(plane, bx % self.width(), row, *byte, wrap);
+            }
+            if flag != 0 { rows |= 1 << i }
+        }
+        rows
+    }
+    /// maps a sprite row onto the screen, None if it is clipped
+    fn sprite_row(&self, y: usize, wrap: bool) -> Option<usize> {
+        if y < self.height() { return Some(y) }
+        if wrap { Some(y % self.height()) } else { None }
     }
     /// returns a collision flag
-    fn blit_byte(&mut self, x: usize, y: usize, mut data: u8) -> u8 {
-        let px = x + y * SCREEN_WIDTH;
-        let offset = px % 8;
-        let i = px / 8;
-        if i >= SCREEN_BUFFER_SIZE { return 0 }
-        let flag;
+    fn blit_byte(&mut self, plane: usize, x: usize, y: usize, data: u8, wrap: bool) -> u8 {
+        let cols = self.width() / 8;
+        let i = y * cols + x / 8;
+        if i >= self.size() { return 0 }
+        let offset = x % 8;
+        let buffer = &mut self.buffers[plane];
 
-        if (x + 8) / SCREEN_WIDTH != 0 {
-            data &= 0xFFu8 << offset;
-        }
+        let left = data >> offset;
+        let mut flag = buffer[i] & left;
+        buffer[i] ^= left;
+        if offset == 0 { return flag }
 
-        if offset == 0 {
-            flag = self.buffer[i] & data;
-            self.buffer[i] ^= data;
+        // the remaining bits either spill into the next byte,
+        // wrap to the start of the row or get clipped
+        let next = if x / 8 + 1 < cols {
+            i + 1
+        } else if wrap {
+            y * cols
         } else {
-            let mut b = self.buffer[i] << offset;
-            self.buffer[i] ^= data >> offset;
-            if i + 1 < SCREEN_BUFFER_SIZE {
-                b |= self.buffer[i+1] >> (8 - offset);
-                self.buffer[i + 1] ^= data << (8 - offset);
-            }
-            flag = b & data;
-        }
+            return flag
+        };
+        let right = data << (8 - offset);
+        flag |= buffer[next] & right;
+        buffer[next] ^= right;
         flag
     }
 }
@@ -59,99 +193,100 @@ impl Display {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::globals::SCREEN_BUFFER_SIZE;
     #[test]
     fn blit_byte() {
         let mut display = Display::new();
-        let flag = display.blit_byte(8, 0, 0b10101011);
+        let flag = display.blit_byte(0, 8, 0, 0b10101011, false);
         assert!(flag == 0x0);
-        assert!(display.buffer[0] == 0x0);
-        assert!(display.buffer[1] == 0b10101011);
-        assert!(display.buffer[2] == 0x0);
+        assert!(display.buffers[0][0] == 0x0);
+        assert!(display.buffers[0][1] == 0b10101011);
+        assert!(display.buffers[0][2] == 0x0);
     }
     #[test]
     fn blit_byte_with_y() {
         let mut display = Display::new();
-        let flag = display.blit_byte(8, 2, 0b10101011);
+        let flag = display.blit_byte(0, 8, 2, 0b10101011, false);
         let target = (8 + 2 * 64) / 8;
         assert!(flag == 0x0);
-        assert!(display.buffer[target-1] == 0x0);
-        assert!(display.buffer[target] == 0b10101011);
-        assert!(display.buffer[target+1] == 0x0);
+        assert!(display.buffers[0][target-1] == 0x0);
+        assert!(display.buffers[0][target] == 0b10101011);
+        assert!(display.buffers[0][target+1] == 0x0);
     }
     #[test]
     fn blit_byte_non_empty() {
         let mut display = Display::new();
-        display.buffer[1] = 0b11011111;
-        let flag = display.blit_byte(8, 0, 0b10111111);
+        display.buffers[0][1] = 0b11011111;
+        let flag = display.blit_byte(0, 8, 0, 0b10111111, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[0] == 0x0);
-        assert!(display.buffer[1] == 0b01100000);
-        assert!(display.buffer[2] == 0x0);
+        assert!(display.buffers[0][0] == 0x0);
+        assert!(display.buffers[0][1] == 0b01100000);
+        assert!(display.buffers[0][2] == 0x0);
     }
     #[test]
     fn blit_byte_unaligned() {
         let mut display = Display::new();
-        let flag = display.blit_byte(2, 0, 0b10101011);
+        let flag = display.blit_byte(0, 2, 0, 0b10101011, false);
         assert!(flag == 0x0);
-        assert!(display.buffer[0] == 0b00101010);
-        assert!(display.buffer[1] == 0b11000000);
-        assert!(display.buffer[2] == 0x0);
+        assert!(display.buffers[0][0] == 0b00101010);
+        assert!(display.buffers[0][1] == 0b11000000);
+        assert!(display.buffers[0][2] == 0x0);
     }
     #[test]
     fn blit_byte_unaligned_with_y() {
         let mut display = Display::new();
-        let flag = display.blit_byte(2, 2, 0b10101011);
+        let flag = display.blit_byte(0, 2, 2, 0b10101011, false);
         let target = (2 + 2 * 64) / 8;
         assert!(flag == 0x0);
-        assert!(display.buffer[target] == 0b00101010);
-        assert!(display.buffer[target+1] == 0b11000000);
-        assert!(display.buffer[target+2] == 0x0);
+        assert!(display.buffers[0][target] == 0b00101010);
+        assert!(display.buffers[0][target+1] == 0b11000000);
+        assert!(display.buffers[0][target+2] == 0x0);
     }
     #[test]
     fn blit_byte_unaligned_non_empty() {
         let mut display = Display::new();
-        display.buffer[1] = 0b10111111;
-        let flag = display.blit_byte(2, 0, 0b10101011);
+        display.buffers[0][1] = 0b10111111;
+        let flag = display.blit_byte(0, 2, 0, 0b10101011, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[0] == 0b00101010);
-        assert!(display.buffer[1] == 0b01111111);
-        assert!(display.buffer[2] == 0x0);
+        assert!(display.buffers[0][0] == 0b00101010);
+        assert!(display.buffers[0][1] == 0b01111111);
+        assert!(display.buffers[0][2] == 0x0);
     }
     #[test]
     fn blit_byte_trim_x() {
         let mut display = Display::new();
-        let flag = display.blit_byte(59, 0, 0b10101011);
+        let flag = display.blit_byte(0, 59, 0, 0b10101011, false);
         let target = 60 / 8;
         assert!(flag == 0x0);
-        assert!(display.buffer[target] == 0b00010101);
-        assert!(display.buffer[target+1] == 0x0);
+        assert!(display.buffers[0][target] == 0b00010101);
+        assert!(display.buffers[0][target+1] == 0x0);
     }
     #[test]
     fn blit_byte_exceed_buffer() {
         let mut display = Display::new();
-        let flag = display.blit_byte(SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, 0b10101011);
+        let flag = display.blit_byte(0, SCREEN_WIDTH - 1, SCREEN_HEIGHT - 1, 0b10101011, false);
         assert!(flag == 0x0);
-        assert!(display.buffer[SCREEN_BUFFER_SIZE - 1] == 0b00000001);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - 1] == 0b00000001);
     }
     #[test]
     fn blit_byte_trim_x_non_empty() {
         let mut display = Display::new();
         let target = 60 / 8;
-        display.buffer[target+1] = 0b11101110;
-        let flag = display.blit_byte(59, 0, 0b10101011);
+        display.buffers[0][target+1] = 0b11101110;
+        let flag = display.blit_byte(0, 59, 0, 0b10101011, false);
         assert!(flag == 0x0);
-        assert!(display.buffer[target] == 0b00010101);
-        assert!(display.buffer[target+1] == 0b11101110);
+        assert!(display.buffers[0][target] == 0b00010101);
+        assert!(display.buffers[0][target+1] == 0b11101110);
     }
     #[test]
     fn blit_sprite_one_line() {
         let mut display = Display::new();
-        let flag = display.blit_sprite(8, 2, &[0b10101011], 1);
+        let flag = display.blit_sprite(8, 2, &[0b10101011], 1, false);
         assert!(flag == 0x0);
         let target = (8 + 2 * 64) / 8;
-        assert!(display.buffer[target-1] == 0x0);
-        assert!(display.buffer[target] == 0b10101011);
-        assert!(display.buffer[target+1] == 0x0);
+        assert!(display.buffers[0][target-1] == 0x0);
+        assert!(display.buffers[0][target] == 0b10101011);
+        assert!(display.buffers[0][target+1] == 0x0);
     }
     #[test]
     fn blit_sprite_multi_line() {
@@ -161,52 +296,206 @@ mod tests {
             0b11101011,
             0b10111011,
         ];
-        let flag = display.blit_sprite(8, 2, &sprite, 3);
+        let flag = display.blit_sprite(8, 2, &sprite, 3, false);
         assert!(flag == 0x0);
         let target = (8 + 2 * SCREEN_WIDTH) / 8;
         let row_offset = SCREEN_WIDTH / 8;
-        assert!(display.buffer[target-row_offset] == 0x0);
-        assert!(display.buffer[target-1] == 0x0);
-        assert!(display.buffer[target] == 0b10101011);
-        assert!(display.buffer[target+1] == 0x0);
-        assert!(display.buffer[target+row_offset] == 0b11101011);
-        assert!(display.buffer[target + 2*row_offset] == 0b10111011);
-        assert!(display.buffer[target + 3*row_offset] == 0x0);
+        assert!(display.buffers[0][target-row_offset] == 0x0);
+        assert!(display.buffers[0][target-1] == 0x0);
+        assert!(display.buffers[0][target] == 0b10101011);
+        assert!(display.buffers[0][target+1] == 0x0);
+        assert!(display.buffers[0][target+row_offset] == 0b11101011);
+        assert!(display.buffers[0][target + 2*row_offset] == 0b10111011);
+        assert!(display.buffers[0][target + 3*row_offset] == 0x0);
     }
     #[test]
     fn blit_sprite_collision_right() {
         let mut display = Display::new();
-        display.buffer[SCREEN_BUFFER_SIZE - 2] = 0b00111111;
+        display.buffers[0][SCREEN_BUFFER_SIZE - 2] = 0b00111111;
         let sprite = [0b10000000];
-        let flag = display.blit_sprite(SCREEN_WIDTH - 9, SCREEN_HEIGHT - 1, &sprite, 1);
+        let flag = display.blit_sprite(SCREEN_WIDTH - 9, SCREEN_HEIGHT - 1, &sprite, 1, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[SCREEN_BUFFER_SIZE - 2] == 0b00111110);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - 2] == 0b00111110);
     }
     #[test]
     fn blit_sprite_collision_left() {
         let mut display = Display::new();
-        display.buffer[SCREEN_BUFFER_SIZE - 2] = 0b11110000;
+        display.buffers[0][SCREEN_BUFFER_SIZE - 2] = 0b11110000;
         let sprite = [0b10000000];
-        let flag = display.blit_sprite(SCREEN_WIDTH - 16, SCREEN_HEIGHT - 1, &sprite, 1);
+        let flag = display.blit_sprite(SCREEN_WIDTH - 16, SCREEN_HEIGHT - 1, &sprite, 1, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[SCREEN_BUFFER_SIZE - 2] == 0b01110000);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - 2] == 0b01110000);
     }
     #[test]
     fn blit_sprite_collision_right_1_1() {
         let mut display = Display::new();
-        display.buffer[SCREEN_BUFFER_SIZE - 2] = 0b00000001;
+        display.buffers[0][SCREEN_BUFFER_SIZE - 2] = 0b00000001;
         let sprite = [0b10000000];
-        let flag = display.blit_sprite(SCREEN_WIDTH - 9, SCREEN_HEIGHT - 1, &sprite, 1);
+        let flag = display.blit_sprite(SCREEN_WIDTH - 9, SCREEN_HEIGHT - 1, &sprite, 1, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[SCREEN_BUFFER_SIZE - 2] == 0b00000000);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - 2] == 0b00000000);
     }
     #[test]
     fn blit_sprite_collision_left_1_1() {
         let mut display = Display::new();
-        display.buffer[SCREEN_BUFFER_SIZE - 2] = 0b10000000;
+        display.buffers[0][SCREEN_BUFFER_SIZE - 2] = 0b10000000;
         let sprite = [0b10000000];
-        let flag = display.blit_sprite(SCREEN_WIDTH - 16, SCREEN_HEIGHT - 1, &sprite, 1);
+        let flag = display.blit_sprite(SCREEN_WIDTH - 16, SCREEN_HEIGHT - 1, &sprite, 1, false);
         assert!(flag != 0x0);
-        assert!(display.buffer[SCREEN_BUFFER_SIZE - 2] == 0b00000000);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - 2] == 0b00000000);
+    }
+    #[test]
+    fn blit_byte_wrap_x() {
+        let mut display = Display::new();
+        let flag = display.blit_byte(0, 60, 1, 0b10101011, true);
+        let row = SCREEN_WIDTH / 8;
+        assert!(flag == 0x0);
+        assert!(display.buffers[0][2 * row - 1] == 0b00001010);
+        assert!(display.buffers[0][row] == 0b10110000);
+    }
+    #[test]
+    fn blit_sprite_wrap_y() {
+        let mut display = Display::new();
+        let sprite = [0b10000000, 0b01000000];
+        let flag = display.blit_sprite(0, SCREEN_HEIGHT - 1, &sprite, 2, true);
+        assert!(flag == 0x0);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - SCREEN_WIDTH / 8] == 0b10000000);
+        assert!(display.buffers[0][0] == 0b01000000);
+    }
+    #[test]
+    fn blit_sprite_clip_y() {
+        let mut display = Display::new();
+        let sprite = [0b10000000, 0b01000000];
+        let flag = display.blit_sprite(0, SCREEN_HEIGHT - 1, &sprite, 2, false);
+        assert!(flag == 0x0);
+        assert!(display.buffers[0][SCREEN_BUFFER_SIZE - SCREEN_WIDTH / 8] == 0b10000000);
+        assert!(display.buffers[0][0] == 0x0);
+    }
+    #[test]
+    fn blit_sprite_start_wraps() {
+        let mut display = Display::new();
+        let flag = display.blit_sprite(SCREEN_WIDTH + 8, SCREEN_HEIGHT, &[0b10101011], 1, false);
+        assert!(flag == 0x0);
+        assert!(display.buffers[0][1] == 0b10101011);
+    }
+    #[test]
+    fn blit_sprite_collision_rows() {
+        let mut display = Display::new();
+        display.buffers[0][0] = 0b10000000;
+        display.buffers[0][2 * SCREEN_WIDTH / 8] = 0b10000000;
+        let sprite = [0b10000000; 3];
+        let rows = display.blit_sprite(0, 0, &sprite, 3, false);
+        assert!(rows == 2);
+    }
+    #[test]
+    fn set_hires() {
+        let mut display = Display::new();
+        display.buffers[0][0] = 0xFF;
+        display.set_hires(true);
+        assert!(display.width() == HIRES_SCREEN_WIDTH);
+        assert!(display.height() == HIRES_SCREEN_HEIGHT);
+        assert!(display.get_buffer() == [0u8; HIRES_SCREEN_BUFFER_SIZE]);
+    }
+    #[test]
+    fn blit_sprite_16() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        let mut sprite = [0u8; 32];
+        sprite[0] = 0b10000001;
+        sprite[1] = 0b11000011;
+        sprite[31] = 0b00000001;
+        let rows = display.blit_sprite_16(8, 1, &sprite, false);
+        let row = HIRES_SCREEN_WIDTH / 8;
+        assert!(rows == 0);
+        assert!(display.buffers[0][row + 1] == 0b10000001);
+        assert!(display.buffers[0][row + 2] == 0b11000011);
+        assert!(display.buffers[0][16 * row + 2] == 0b00000001);
+        let rows = display.blit_sprite_16(8, 1, &sprite, false);
+        assert!(rows == 2);
+        assert!(display.get_buffer() == [0u8; HIRES_SCREEN_BUFFER_SIZE]);
+    }
+    #[test]
+    fn blit_sprite_16_clip_x() {
+        let mut display = Display::new();
+        let sprite = [0xFF; 32];
+        display.blit_sprite_16(SCREEN_WIDTH - 8, 0, &sprite, false);
+        assert!(display.buffers[0][SCREEN_WIDTH / 8 - 1] == 0xFF);
+        assert!(display.buffers[0][0] == 0x0);
+    }
+    #[test]
+    fn scroll_down() {
+        let mut display = Display::new();
+        display.buffers[0][1] = 0b10101011;
+        display.scroll_down(2);
+        let row = SCREEN_WIDTH / 8;
+        assert!(display.buffers[0][1] == 0x0);
+        assert!(display.buffers[0][2 * row + 1] == 0b10101011);
+    }
+    #[test]
+    fn scroll_down_past_screen() {
+        let mut display = Display::new();
+        display.buffers[0][1] = 0b10101011;
+        display.scroll_down(SCREEN_HEIGHT + 1);
+        assert!(display.get_buffer() == [0u8; SCREEN_BUFFER_SIZE]);
+    }
+    #[test]
+    fn scroll_right() {
+        let mut display = Display::new();
+        let row = SCREEN_WIDTH / 8;
+        display.buffers[0][row] = 0b10101011;
+        display.buffers[0][2 * row - 1] = 0b00001111;
+        display.scroll_right();
+        assert!(display.buffers[0][row] == 0b00001010);
+        assert!(display.buffers[0][row + 1] == 0b10110000);
+        assert!(display.buffers[0][2 * row - 1] == 0b00000000);
+    }
+    #[test]
+    fn scroll_left() {
+        let mut display = Display::new();
+        let row = SCREEN_WIDTH / 8;
+        display.buffers[0][row] = 0b10101011;
+        display.buffers[0][row + 1] = 0b11000000;
+        display.scroll_left();
+        assert!(display.buffers[0][row] == 0b10111100);
+        assert!(display.buffers[0][row + 1] == 0b00000000);
+        assert!(display.buffers[0][row - 1] == 0b00000000);
+    }
+    #[test]
+    fn scroll_up() {
+        let mut display = Display::new();
+        let row = SCREEN_WIDTH / 8;
+        display.buffers[0][2 * row + 1] = 0b10101011;
+        display.scroll_up(2);
+        assert!(display.buffers[0][1] == 0b10101011);
+        assert!(display.buffers[0][2 * row + 1] == 0x0);
+    }
+    #[test]
+    fn blit_sprite_planes() {
+        let mut display = Display::new();
+        display.select_planes(0b11);
+        let flag = display.blit_sprite(8, 0, &[0b10101011, 0b11110000], 1, false);
+        assert!(flag == 0x0);
+        assert!(display.buffers[0][1] == 0b10101011);
+        assert!(display.buffers[1][1] == 0b11110000);
+    }
+    #[test]
+    fn blit_sprite_second_plane() {
+        let mut display = Display::new();
+        display.select_planes(0b10);
+        display.blit_sprite(8, 0, &[0b10101011], 1, false);
+        assert!(display.buffers[0][1] == 0x0);
+        assert!(display.buffers[1][1] == 0b10101011);
+        assert!(display.get_plane_buffer(1).unwrap()[1] == 0b10101011);
+        assert!(display.get_plane_buffer(PLANE_COUNT).is_none());
+    }
+    #[test]
+    fn clear_selected_planes() {
+        let mut display = Display::new();
+        display.buffers[0][0] = 0xFF;
+        display.buffers[1][0] = 0xFF;
+        display.select_planes(0b10);
+        display.clear();
+        assert!(display.buffers[0][0] == 0xFF);
+        assert!(display.buffers[1][0] == 0x0);
     }
 }
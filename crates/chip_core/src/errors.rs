@@ -1,9 +1,126 @@
-#[derive(Debug, PartialEq)]
-pub enum ChipError {
+use core::fmt;
+
+/// A fault raised by the instruction at `pc`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChipError {
+    pub kind: ErrorKind,
+    pub pc: u16,
+    /// None if the instruction could not be fetched
+    pub opcode: Option<u16>
+}
+impl fmt::Display for ChipError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {:#05X}", self.kind, self.pc)?;
+        if let Some(opcode) = self.opcode {
+            write!(f, " ({:04X})", opcode)?;
+        }
+        Ok(())
+    }
+}
+impl core::error::Error for ChipError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
     IllegalInst(u16),
     IllegalAddr(u16),
     IllegalReg(u8),
     IllegalKey(u8),
     StackOverflow,
-    StackUnderflow
+    StackUnderflow,
+    /// holds the ROM size
+    RomTooLarge(usize)
+}
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::IllegalInst(word) => write!(f, "illegal instruction {:04X}", word),
+            ErrorKind::IllegalAddr(addr) => write!(f, "illegal address {:#05X}", addr),
+            ErrorKind::IllegalReg(reg) => write!(f, "illegal register V{:X}", reg),
+            ErrorKind::IllegalKey(key) => write!(f, "illegal key {:#04X}", key),
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::RomTooLarge(size) => write!(f, "ROM of {} bytes does not fit in memory", size)
+        }
+    }
+}
+impl core::error::Error for ErrorKind {}
+
+/// What `Cpu::step` does after a fault
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnError {
+    /// stay on the faulting instruction, later steps return the same error
+    #[default]
+    Halt,
+    /// treat the instruction as a no-op
+    Skip,
+    /// return the error and resume after the instruction on the next step
+    Trap
+}
+
+/// `OnError` for each kind of fault
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ErrorPolicy {
+    pub illegal_inst: OnError,
+    pub illegal_addr: OnError,
+    pub illegal_reg: OnError,
+    pub illegal_key: OnError,
+    pub stack_overflow: OnError,
+    pub stack_underflow: OnError
+}
+impl ErrorPolicy {
+    /// The same handling for every fault
+    pub fn all(on_error: OnError) -> Self {
+        ErrorPolicy {
+            illegal_inst: on_error,
+            illegal_addr: on_error,
+            illegal_reg: on_error,
+            illegal_key: on_error,
+            stack_overflow: on_error,
+            stack_underflow: on_error
+        }
+    }
+    pub fn get(&self, kind: ErrorKind) -> OnError {
+        match kind {
+            ErrorKind::IllegalInst(_) => self.illegal_inst,
+            ErrorKind::IllegalAddr(_) => self.illegal_addr,
+            ErrorKind::IllegalReg(_) => self.illegal_reg,
+            ErrorKind::IllegalKey(_) => self.illegal_key,
+            ErrorKind::StackOverflow => self.stack_overflow,
+            ErrorKind::StackUnderflow => self.stack_underflow,
+            ErrorKind::RomTooLarge(_) => OnError::Halt
+        }
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+pub enum SnapshotError {
+    /// holds the required buffer size
+    BufferTooSmall(usize),
+    BadMagic,
+    UnsupportedVersion(u8),
+    Corrupted
+}
+
+
+#[derive(Debug, PartialEq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Corrupted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate alloc;
+    use alloc::string::ToString;
+
+    #[test]
+    fn display() {
+        let error = ChipError { kind: ErrorKind::IllegalInst(0x5001), pc: 0x200, opcode: Some(0x5001) };
+        assert!(error.to_string() == "illegal instruction 5001 at 0x200 (5001)");
+        let error = ChipError { kind: ErrorKind::IllegalAddr(0x1000), pc: 0xfff, opcode: None };
+        assert!(error.to_string() == "illegal address 0x1000 at 0xFFF");
+    }
 }
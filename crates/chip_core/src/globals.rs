@@ -1,12 +1,23 @@
 pub const RAM_SIZE: usize = 4096;
+pub const XO_RAM_SIZE: usize = 0x10000;
 pub const STACK_SIZE: usize = 16;
 pub const REG_COUNT: usize = 16;
+pub const RPL_COUNT: usize = 16;
+pub const KEY_QUEUE_SIZE: usize = 16;
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 pub const SCREEN_BUFFER_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT / 8;
 
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+pub const HIRES_SCREEN_BUFFER_SIZE: usize = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT / 8;
+pub const PLANE_COUNT: usize = 2;
+
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+
 pub const FONT_ADDR: u16 = 0x0050;
+pub const BIG_FONT_ADDR: u16 = 0x00A0;
 
-// QUIRKS
-pub const SHIFT_OP_USE_VY: bool = false;
\ No newline at end of file
+pub const DEFAULT_RANDOM_SEED: u32 = 0x5321a409;
@@ -0,0 +1,185 @@
+//! Keypad events.
+//!
+//! Key presses and releases are queued and applied one per instruction,
+//! so a tap shorter than a step is still seen by the program.
+use crate::{
+    cpu::{Cpu, RunState},
+    errors::ErrorKind,
+    globals::KEY_QUEUE_SIZE
+};
+
+/// Queued events are stored as the key + `KEY_DOWN` when pressed
+pub(crate) const KEY_DOWN: u8 = 0x10;
+
+/// Fixed size ring buffer of key events
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct KeyQueue {
+    pub(crate) events: [u8; KEY_QUEUE_SIZE],
+    pub(crate) start: usize,
+    pub(crate) len: usize
+}
+impl KeyQueue {
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn is_full(&self) -> bool {
+        self.len == KEY_QUEUE_SIZE
+    }
+    pub fn push(&mut self, event: u8) {
+        self.events[(self.start + self.len) % KEY_QUEUE_SIZE] = event;
+        self.len += 1;
+    }
+    pub fn pop(&mut self) -> Option<u8> {
+        if self.is_empty() { return None }
+        let event = self.events[self.start];
+        self.start = (self.start + 1) % KEY_QUEUE_SIZE;
+        self.len -= 1;
+        Some(event)
+    }
+    pub fn iter(&self) -> impl Iterator<Item=u8> + '_ {
+        (0..self.len).map(|i| self.events[(self.start + i) % KEY_QUEUE_SIZE])
+    }
+}
+
+impl Cpu {
+    pub fn key_down(&mut self, key: u8) -> Result<(), ErrorKind> {
+        self.queue_key(key, true)
+    }
+    pub fn key_up(&mut self, key: u8) -> Result<(), ErrorKind> {
+        self.queue_key(key, false)
+    }
+    /// Queues the changes needed to reach the given keypad state
+    pub fn set_keys(&mut self, keys: [bool; 0x10]) {
+        let mut current = self.keys;
+        for event in self.key_queue.iter() {
+            current[(event & 0xF) as usize] = event & KEY_DOWN != 0;
+        }
+        for (key, (&now, &next)) in current.iter().zip(keys.iter()).enumerate() {
+            if now != next {
+                let _ = self.queue_key(key as u8, next);
+            }
+        }
+    }
+    fn queue_key(&mut self, key: u8, down: bool) -> Result<(), ErrorKind> {
+        if key > 0xF { return Err(ErrorKind::IllegalKey(key)) }
+        // make room by applying the oldest event early
+        if self.key_queue.is_full() { self.apply_key_event(); }
+        self.key_queue.push(key | if down { KEY_DOWN } else { 0 });
+        Ok(())
+    }
+    /// Applies the oldest queued event, called before each instruction
+    pub(crate) fn apply_key_event(&mut self) {
+        let Some(event) = self.key_queue.pop() else { return };
+        let key = event & 0xF;
+        let down = event & KEY_DOWN != 0;
+        self.keys[key as usize] = down;
+        let RunState::WaitingForKey(x) = self.state else { return };
+        // FX0A completes on the press, or on the release of the key pressed while waiting
+        let done = if self.quirks.key_wait_press {
+            down
+        } else if down {
+            self.wait_key.get_or_insert(key);
+            false
+        } else {
+            self.wait_key == Some(key)
+        };
+        if done {
+            self.v[x as usize & 0xF] = key;
+            self.wait_key = None;
+            self.state = RunState::Running;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    #[test]
+    fn queue_wraps() {
+        let mut queue = KeyQueue::default();
+        for i in 0..KEY_QUEUE_SIZE as u8 {
+            queue.push(i);
+        }
+        assert!(queue.is_full());
+        assert!(queue.pop() == Some(0));
+        queue.push(0x1F);
+        assert!(queue.iter().last() == Some(0x1F));
+        assert!(queue.iter().count() == KEY_QUEUE_SIZE);
+    }
+    #[test]
+    fn tap_between_steps() {
+        let mut cpu = Cpu::new();
+        // SKP V0, then LD V1 1 on the skipped path
+        cpu.load_rom(0x200, &[0xe0, 0x9e, 0x61, 0x01, 0x61, 0x02]).unwrap();
+        cpu.key_down(0).unwrap();
+        cpu.key_up(0).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.v[1] == 2);
+        assert!(!cpu.keys()[0]);
+    }
+    #[test]
+    fn illegal_key() {
+        let mut cpu = Cpu::new();
+        assert!(cpu.key_down(0x10) == Err(ErrorKind::IllegalKey(0x10)));
+    }
+    #[test]
+    fn full_queue_applies_oldest() {
+        let mut cpu = Cpu::new();
+        for _ in 0..KEY_QUEUE_SIZE / 2 {
+            cpu.key_down(3).unwrap();
+            cpu.key_up(3).unwrap();
+        }
+        cpu.key_down(5).unwrap();
+        assert!(cpu.keys()[3]);
+        assert!(cpu.key_queue.len == KEY_QUEUE_SIZE);
+    }
+    #[test]
+    fn set_keys_queues_changes() {
+        let mut cpu = Cpu::new();
+        let mut keys = [false; 0x10];
+        keys[2] = true;
+        keys[9] = true;
+        cpu.set_keys(keys);
+        cpu.set_keys(keys);
+        assert!(cpu.key_queue.len == 2);
+        keys[2] = false;
+        cpu.set_keys(keys);
+        assert!(cpu.key_queue.iter().eq([2 | KEY_DOWN, 9 | KEY_DOWN, 2]));
+    }
+    #[test]
+    fn key_wait_release() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0xf4, 0x0a]).unwrap();
+        cpu.key_down(1).unwrap();
+        cpu.step().unwrap();
+        // a key held before FX0A does not count
+        cpu.key_up(1).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.run_state() == &RunState::WaitingForKey(4));
+        cpu.key_down(7).unwrap();
+        cpu.key_down(8).unwrap();
+        cpu.key_up(8).unwrap();
+        cpu.key_up(7).unwrap();
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert!(cpu.run_state() == &RunState::WaitingForKey(4));
+        cpu.step().unwrap();
+        assert!(cpu.run_state() == &RunState::Running);
+        assert!(cpu.v[4] == 7);
+    }
+    #[test]
+    fn key_wait_press() {
+        let mut cpu = Cpu::with_quirks(Quirks { key_wait_press: true, ..Default::default() });
+        cpu.load_rom(0x200, &[0xf4, 0x0a]).unwrap();
+        cpu.step().unwrap();
+        cpu.key_down(0xc).unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.run_state() == &RunState::Running);
+        assert!(cpu.v[4] == 0xc);
+    }
+}
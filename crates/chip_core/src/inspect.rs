@@ -0,0 +1,93 @@
+//! Read-only views of the machine state and checked memory access for tools.
+use crate::{
+    cpu::Cpu,
+    errors::ErrorKind,
+    globals::RPL_COUNT
+};
+
+impl Cpu {
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+    /// Return addresses currently on the stack, the innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp.min(self.stack.len())]
+    }
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+    pub fn keys(&self) -> [bool; 0x10] {
+        self.keys
+    }
+    /// SCHIP persistent flag registers
+    pub fn rpl(&self) -> &[u8; RPL_COUNT] {
+        &self.rpl
+    }
+    /// Memory addressable on the current platform
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.ram_size()]
+    }
+    /// `len` bytes starting at `addr`, an error if they run past the RAM
+    pub fn memory_slice(&self, addr: u16, len: usize) -> Result<&[u8], ErrorKind> {
+        self.memory().get(addr as usize..addr as usize + len).ok_or(ErrorKind::IllegalAddr(addr))
+    }
+    pub fn peek(&self, addr: u16) -> Result<u8, ErrorKind> {
+        self.memory().get(addr as usize).copied().ok_or(ErrorKind::IllegalAddr(addr))
+    }
+    /// Writes a byte, keeping the decode cache coherent
+    pub fn poke(&mut self, addr: u16, val: u8) -> Result<(), ErrorKind> {
+        self.write(addr as usize, val)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{globals::RAM_SIZE, quirks::Preset};
+
+    #[test]
+    fn registers_and_stack() {
+        let mut cpu = Cpu::new();
+        // CALL 0x204, LD I 0x123, CALL 0x208
+        cpu.load_rom(0x200, &[0x22, 0x04, 0x00, 0x00, 0xa1, 0x23, 0x22, 0x08]).unwrap();
+        for _ in 0..3 {
+            cpu.step().unwrap();
+        }
+        assert!(cpu.pc() == 0x208);
+        assert!(cpu.i() == 0x123);
+        assert!(cpu.sp() == 2);
+        assert!(cpu.stack() == [0x202, 0x208]);
+    }
+    #[test]
+    fn peek_and_poke() {
+        let mut cpu = Cpu::new();
+        assert!(cpu.poke(0x300, 0xab) == Ok(()));
+        assert!(cpu.peek(0x300) == Ok(0xab));
+        assert!(cpu.memory_slice(0x2ff, 3) == Ok(&[0, 0xab, 0][..]));
+        assert!(cpu.peek(RAM_SIZE as u16) == Err(ErrorKind::IllegalAddr(RAM_SIZE as u16)));
+        assert!(cpu.poke(RAM_SIZE as u16, 1) == Err(ErrorKind::IllegalAddr(RAM_SIZE as u16)));
+        assert!(cpu.memory_slice(0xffe, 4) == Err(ErrorKind::IllegalAddr(0xffe)));
+        // XO-CHIP addresses the full 64 KiB
+        let cpu = Cpu::with_preset(Preset::XoChip);
+        assert!(cpu.peek(0xffff) == Ok(0));
+    }
+    #[test]
+    fn poke_invalidates_code() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x60, 0x01, 0x12, 0x00]).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.poke(0x201, 0x02).unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.v[0] == 0x02);
+    }
+}
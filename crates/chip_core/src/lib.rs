@@ -1,9 +1,28 @@
 #![no_std]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod audio;
 mod cpu;
+#[cfg(feature = "alloc")]
+pub mod debugger;
+pub mod disasm;
 mod display;
 mod errors;
 mod font;
 pub mod globals;
+mod input;
+mod inspect;
+#[cfg(feature = "alloc")]
+pub mod movie;
+mod quirks;
+pub mod random;
+#[cfg(feature = "alloc")]
+pub mod rewind;
+pub mod snapshot;
+pub mod timing;
 mod utils;
 
-pub use cpu::Cpu;
+pub use cpu::{Cpu, Frame, RunState};
+pub use errors::{ChipError, ErrorKind, ErrorPolicy, MovieError, OnError, SnapshotError};
+pub use quirks::{Platform, Preset, Quirks};
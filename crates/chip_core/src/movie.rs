@@ -0,0 +1,209 @@
+//! Input movies: everything needed to replay a session deterministically.
+//!
+//! A frame is `steps_per_frame` instructions followed by a single timer tick,
+//! or a frame of COSMAC VIP machine time when `steps_per_frame` is 0.
+//! Each frame stores the keypad state wanted at its start. Playback passes
+//! it to `Cpu::set_keys`, which queues a press or release for every key that
+//! differs, in key order. The events are then applied one per instruction,
+//! so a change can take a few steps to reach the program. Recording must feed
+//! the keys through `set_keys` once per frame too for the timing to match.
+//!
+//! Binary layout (little-endian):
+//!
+//! | offset | size | field                                   |
+//! |--------|------|-----------------------------------------|
+//! | 0      | 4    | magic `C8MV`                            |
+//! | 4      | 1    | format version                          |
+//! | 5      | 1    | platform                                |
+//! | 6      | 1    | quirks, see `Quirks::to_bits`           |
+//! | 7      | 1    | reserved                                |
+//! | 8      | 4    | FNV-1a hash of the ROM                  |
+//! | 12     | 4    | random seed                             |
+//! | 16     | 2    | ROM load address                        |
+//! | 18     | 2    | steps per frame, 0 for VIP timing       |
+//! | 20     | 4    | frame count                             |
+//! | 24     | 2*n  | key state of every frame, bit per key   |
+use alloc::vec::Vec;
+
+use crate::{
+    cpu::Cpu,
+    errors::{ChipError, ErrorKind, MovieError},
+    quirks::{Platform, Quirks},
+    utils::{keys_to_bits, keys_from_bits}
+};
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"C8MV";
+pub const MOVIE_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 24;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Movie {
+    pub rom_hash: u32,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    pub seed: u32,
+    pub load_addr: u16,
+    pub steps_per_frame: u16,
+    pub frames: Vec<u16>
+}
+impl Movie {
+    /// Starts an empty recording for the given ROM and machine settings
+    pub fn new(rom: &[u8], cpu: &Cpu, seed: u32, load_addr: u16, steps_per_frame: u16) -> Self {
+        Movie {
+            rom_hash: rom_hash(rom),
+            platform: cpu.platform(),
+            quirks: cpu.quirks(),
+            seed,
+            load_addr,
+            steps_per_frame,
+            frames: Vec::new()
+        }
+    }
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_hash == rom_hash(rom)
+    }
+    /// Creates a machine in the initial state of the recording
+    pub fn start(&self, rom: &[u8]) -> Result<Cpu, ErrorKind> {
+        let mut cpu = Cpu::with_quirks(self.quirks);
+        cpu.set_platform(self.platform);
+        cpu.set_random_seed(self.seed);
+        cpu.load_rom(self.load_addr, rom)?;
+        Ok(cpu)
+    }
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+    pub fn record_frame(&mut self, keys: [bool; 0x10]) {
+        self.frames.push(keys_to_bits(&keys));
+    }
+    pub fn frame_keys(&self, frame: usize) -> Option<[bool; 0x10]> {
+        self.frames.get(frame).map(|bits| keys_from_bits(*bits))
+    }
+    /// Runs a single recorded frame, returns false once the movie has ended
+    pub fn play_frame(&self, cpu: &mut Cpu, frame: usize) -> Result<bool, ChipError> {
+        let Some(keys) = self.frame_keys(frame) else { return Ok(false) };
+        cpu.set_keys(keys);
+        let frame = match self.steps_per_frame {
+            0 => cpu.run_vip_frame(),
+            steps => cpu.run_frame(steps as u32)
+        };
+        match frame.error {
+            Some(e) => Err(e),
+            None => Ok(true)
+        }
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_SIZE + 2 * self.frames.len());
+        out.extend_from_slice(&MOVIE_MAGIC);
+        out.push(MOVIE_VERSION);
+        out.push(self.platform as u8);
+        out.push(self.quirks.to_bits());
+        out.push(0);
+        out.extend_from_slice(&self.rom_hash.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.extend_from_slice(&self.load_addr.to_le_bytes());
+        out.extend_from_slice(&self.steps_per_frame.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for keys in self.frames.iter() {
+            out.extend_from_slice(&keys.to_le_bytes());
+        }
+        out
+    }
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, MovieError> {
+        if data.len() < HEADER_SIZE { return Err(MovieError::Corrupted) }
+        if data[0..4] != MOVIE_MAGIC { return Err(MovieError::BadMagic) }
+        if data[4] != MOVIE_VERSION { return Err(MovieError::UnsupportedVersion(data[4])) }
+        let platform = Platform::from_u8(data[5]).ok_or(MovieError::Corrupted)?;
+        let u16_at = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let count = u32_at(20) as usize;
+        if data.len() != HEADER_SIZE + 2 * count { return Err(MovieError::Corrupted) }
+        Ok(Movie {
+            rom_hash: u32_at(8),
+            platform,
+            quirks: Quirks::from_bits(data[6]),
+            seed: u32_at(12),
+            load_addr: u16_at(16),
+            steps_per_frame: u16_at(18),
+            frames: (0..count).map(|f| u16_at(HEADER_SIZE + 2 * f)).collect()
+        })
+    }
+}
+
+/// 32-bit FNV-1a
+pub fn rom_hash(rom: &[u8]) -> u32 {
+    rom.iter().fold(0x811c9dc5, |hash, b| (hash ^ *b as u32).wrapping_mul(0x01000193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    // moves a random sprite while key 5 is held
+    const ROM: [u8; 14] = [
+        0xc0, 0x3f,
+        0xa0, 0x50,
+        0x65, 0x05,
+        0xe5, 0xa1,
+        0x71, 0x01,
+        0xd0, 0x15,
+        0x12, 0x00
+    ];
+
+    fn record(seed: u32) -> (Movie, Cpu) {
+        let mut cpu = Cpu::with_preset(Preset::CosmacVip);
+        cpu.set_random_seed(seed);
+        cpu.load_rom(0x200, &ROM).unwrap();
+        let mut movie = Movie::new(&ROM, &cpu, seed, 0x200, 9);
+        for frame in 0..30 {
+            let mut keys = [false; 0x10];
+            keys[5] = frame % 3 == 0;
+            movie.record_frame(keys);
+            movie.play_frame(&mut cpu, frame).unwrap();
+        }
+        (movie, cpu)
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let (movie, _) = record(0x1234);
+        let data = movie.to_bytes();
+        assert!(data.len() == HEADER_SIZE + 2 * movie.len());
+        assert!(Movie::from_bytes(&data) == Ok(movie));
+    }
+    #[test]
+    fn from_bytes_invalid() {
+        let (movie, _) = record(0x1234);
+        let mut data = movie.to_bytes();
+        assert!(Movie::from_bytes(&data[..data.len() - 1]) == Err(MovieError::Corrupted));
+        data[4] = MOVIE_VERSION + 1;
+        assert!(Movie::from_bytes(&data) == Err(MovieError::UnsupportedVersion(MOVIE_VERSION + 1)));
+        data[0] = 0;
+        assert!(Movie::from_bytes(&data) == Err(MovieError::BadMagic));
+    }
+    #[test]
+    fn playback_is_deterministic() {
+        let (movie, recorded) = record(0xbeef);
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+        assert!(movie.matches_rom(&ROM));
+        let mut cpu = movie.start(&ROM).unwrap();
+        let mut frame = 0;
+        while movie.play_frame(&mut cpu, frame).unwrap() {
+            frame += 1;
+        }
+        assert!(frame == 30);
+        assert!(cpu.quirks() == Quirks::VIP);
+        assert!(cpu.get_display_buffer() == recorded.get_display_buffer());
+        assert!(cpu.v == recorded.v);
+        assert!(cpu.pc == recorded.pc);
+    }
+    #[test]
+    fn rom_hash_differs() {
+        assert!(rom_hash(&ROM) != rom_hash(&ROM[..12]));
+        assert!(rom_hash(&[]) == 0x811c9dc5);
+    }
+}
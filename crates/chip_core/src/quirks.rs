@@ -0,0 +1,157 @@
+/// Behavioural differences between CHIP-8 interpreters.
+/// The default matches the original behaviour of this core.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 / 8XYE shift VY into VX (instead of shifting VX in place)
+    pub shift_vy: bool,
+    /// 8XY1 / 8XY2 / 8XY3 reset VF to 0
+    pub logic_reset_vf: bool,
+    /// FX55 / FX65 leave I pointing past the last register (I += X + 1)
+    pub memory_increment_i: bool,
+    /// BNNN is treated as BXNN and jumps to XNN + VX (instead of NNN + V0)
+    pub jump_vx: bool,
+    /// sprites wrap around the screen edges (instead of being clipped)
+    pub wrap_sprites: bool,
+    /// DXYN waits for the vertical blank, limiting draws to one per frame
+    pub display_wait: bool,
+    /// FX0A completes on a key press (instead of waiting for its release)
+    pub key_wait_press: bool,
+}
+impl Quirks {
+    pub const VIP: Quirks = Quirks {
+        shift_vy: true,
+        logic_reset_vf: true,
+        memory_increment_i: true,
+        jump_vx: false,
+        wrap_sprites: false,
+        display_wait: true,
+        key_wait_press: false,
+    };
+    pub const CHIP48: Quirks = Quirks {
+        shift_vy: false,
+        logic_reset_vf: false,
+        memory_increment_i: false,
+        jump_vx: true,
+        wrap_sprites: false,
+        display_wait: false,
+        key_wait_press: false,
+    };
+    pub const SCHIP: Quirks = Quirks {
+        shift_vy: false,
+        logic_reset_vf: false,
+        memory_increment_i: false,
+        jump_vx: true,
+        wrap_sprites: false,
+        display_wait: false,
+        key_wait_press: false,
+    };
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_vy: true,
+        logic_reset_vf: false,
+        memory_increment_i: true,
+        jump_vx: false,
+        wrap_sprites: true,
+        display_wait: false,
+        key_wait_press: false,
+    };
+
+    /// packs the quirks into a single byte, used by the binary formats
+    pub fn to_bits(&self) -> u8 {
+        self.shift_vy as u8
+            | (self.logic_reset_vf as u8) << 1
+            | (self.memory_increment_i as u8) << 2
+            | (self.jump_vx as u8) << 3
+            | (self.wrap_sprites as u8) << 4
+            | (self.display_wait as u8) << 5
+            | (self.key_wait_press as u8) << 6
+    }
+    pub fn from_bits(bits: u8) -> Quirks {
+        Quirks {
+            shift_vy: bits & 1 != 0,
+            logic_reset_vf: bits & 1 << 1 != 0,
+            memory_increment_i: bits & 1 << 2 != 0,
+            jump_vx: bits & 1 << 3 != 0,
+            wrap_sprites: bits & 1 << 4 != 0,
+            display_wait: bits & 1 << 5 != 0,
+            key_wait_press: bits & 1 << 6 != 0,
+        }
+    }
+}
+
+/// Instruction set extensions understood by the Cpu
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+impl Platform {
+    pub fn from_u8(val: u8) -> Option<Platform> {
+        match val {
+            0 => Some(Platform::Chip8),
+            1 => Some(Platform::SuperChip),
+            2 => Some(Platform::XoChip),
+            _ => None
+        }
+    }
+}
+
+/// Named quirk profiles of well known interpreters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    CosmacVip,
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::CosmacVip,
+        Preset::Chip48,
+        Preset::SuperChip,
+        Preset::XoChip,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::CosmacVip => "COSMAC VIP",
+            Preset::Chip48 => "CHIP-48",
+            Preset::SuperChip => "SCHIP",
+            Preset::XoChip => "XO-CHIP",
+        }
+    }
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Preset::CosmacVip => Quirks::VIP,
+            Preset::Chip48 => Quirks::CHIP48,
+            Preset::SuperChip => Quirks::SCHIP,
+            Preset::XoChip => Quirks::XO_CHIP,
+        }
+    }
+    pub fn platform(&self) -> Platform {
+        match self {
+            Preset::CosmacVip | Preset::Chip48 => Platform::Chip8,
+            Preset::SuperChip => Platform::SuperChip,
+            Preset::XoChip => Platform::XoChip,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn quirks_bits() {
+        for preset in Preset::ALL {
+            let quirks = preset.quirks();
+            assert!(Quirks::from_bits(quirks.to_bits()) == quirks);
+        }
+    }
+    #[test]
+    fn platform_u8() {
+        for platform in [Platform::Chip8, Platform::SuperChip, Platform::XoChip] {
+            assert!(Platform::from_u8(platform as u8) == Some(platform));
+        }
+        assert!(Platform::from_u8(3).is_none());
+    }
+}
@@ -0,0 +1,146 @@
+//! Random number sources for CXNN.
+//!
+//! A source is reproducible from its seed, and its position in the sequence
+//! is a single `u32` so that snapshots can capture it.
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::globals::DEFAULT_RANDOM_SEED;
+
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+    /// Seed the sequence started from, worth logging to reproduce a run
+    fn seed(&self) -> u32;
+    /// Position in the sequence
+    fn state(&self) -> u32;
+    fn set_state(&mut self, state: u32);
+    /// Whether `state` is a position the source can be in
+    fn is_valid_state(&self, state: u32) -> bool;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: RandomSource + ?Sized> RandomSource for alloc::boxed::Box<T> {
+    fn next_byte(&mut self) -> u8 {
+        (**self).next_byte()
+    }
+    fn seed(&self) -> u32 {
+        (**self).seed()
+    }
+    fn state(&self) -> u32 {
+        (**self).state()
+    }
+    fn set_state(&mut self, state: u32) {
+        (**self).set_state(state)
+    }
+    fn is_valid_state(&self, state: u32) -> bool {
+        (**self).is_valid_state(state)
+    }
+}
+
+/// The xorshift32 generator, the default source
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XorShift {
+    seed: u32,
+    state: u32
+}
+impl XorShift {
+    /// A zero seed would only produce zeros and is replaced by the default
+    pub fn new(seed: u32) -> Self {
+        let seed = if seed == 0 { DEFAULT_RANDOM_SEED } else { seed };
+        XorShift { seed, state: seed }
+    }
+}
+impl Default for XorShift {
+    fn default() -> Self {
+        XorShift::new(DEFAULT_RANDOM_SEED)
+    }
+}
+impl RandomSource for XorShift {
+    fn next_byte(&mut self) -> u8 {
+        let mut val = self.state;
+        val ^= val << 13;
+        val ^= val >> 17;
+        val ^= val << 5;
+        self.state = val;
+        val as u8
+    }
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+    fn state(&self) -> u32 {
+        self.state
+    }
+    fn set_state(&mut self, state: u32) {
+        self.state = state;
+    }
+    /// zero is a fixed point of the generator
+    fn is_valid_state(&self, state: u32) -> bool {
+        state != 0
+    }
+}
+
+/// Repeats a scripted list of values, for tests
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequence {
+    values: Vec<u8>,
+    pos: usize
+}
+#[cfg(feature = "alloc")]
+impl Sequence {
+    pub fn new(values: &[u8]) -> Self {
+        Sequence { values: values.to_vec(), pos: 0 }
+    }
+}
+#[cfg(feature = "alloc")]
+impl RandomSource for Sequence {
+    fn next_byte(&mut self) -> u8 {
+        let Some(&val) = self.values.get(self.pos) else { return 0 };
+        self.pos = (self.pos + 1) % self.values.len();
+        val
+    }
+    fn seed(&self) -> u32 {
+        0
+    }
+    fn state(&self) -> u32 {
+        self.pos as u32
+    }
+    fn set_state(&mut self, state: u32) {
+        self.pos = state as usize % self.values.len().max(1);
+    }
+    fn is_valid_state(&self, state: u32) -> bool {
+        (state as usize) < self.values.len().max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xorshift_zero_seed() {
+        let mut random = XorShift::new(0);
+        assert!(random.seed() == DEFAULT_RANDOM_SEED);
+        assert!(random.next_byte() != 0 || random.next_byte() != 0);
+    }
+    #[test]
+    fn xorshift_state() {
+        let mut random = XorShift::new(0x1234);
+        random.next_byte();
+        let state = random.state();
+        let expected = [random.next_byte(), random.next_byte()];
+        random.set_state(state);
+        assert!([random.next_byte(), random.next_byte()] == expected);
+        assert!(random.seed() == 0x1234);
+    }
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn sequence_repeats() {
+        let mut random = Sequence::new(&[1, 2, 3]);
+        let values: Vec<u8> = (0..5).map(|_| random.next_byte()).collect();
+        assert!(values == [1, 2, 3, 1, 2]);
+        random.set_state(1);
+        assert!(random.next_byte() == 2);
+        assert!(Sequence::new(&[]).next_byte() == 0);
+    }
+}
@@ -0,0 +1,253 @@
+//! Rewind history built on top of snapshots.
+//!
+//! Only the most recent state is kept in full. Every older state is stored
+//! as a delta against the state that followed it: the XOR of both
+//! snapshots, run-length encoded, so unchanged memory costs almost nothing.
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+use crate::{cpu::Cpu, errors::SnapshotError};
+
+pub struct Rewind {
+    interval: usize,
+    capacity: usize,
+    frames: usize,
+    head: Option<Vec<u8>>,
+    // deltas[k] turns state k + 1 back into state k, the oldest is first
+    deltas: VecDeque<Vec<u8>>,
+}
+impl Rewind {
+    /// Records a state every `interval` frames, keeping at most `capacity` states.
+    /// `step_back` moves between recorded states, so it goes back `interval`
+    /// frames at a time, use 1 to rewind frame by frame.
+    pub fn new(interval: usize, capacity: usize) -> Self {
+        Rewind {
+            interval: interval.max(1),
+            capacity: capacity.max(1),
+            frames: 0,
+            head: None,
+            deltas: VecDeque::new(),
+        }
+    }
+    /// Number of recorded states
+    pub fn len(&self) -> usize {
+        if self.head.is_some() { self.deltas.len() + 1 } else { 0 }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+    pub fn clear(&mut self) {
+        self.frames = 0;
+        self.head = None;
+        self.deltas.clear();
+    }
+    /// Approximate memory used by the history in bytes
+    pub fn memory_usage(&self) -> usize {
+        self.head.as_ref().map_or(0, |h| h.len())
+            + self.deltas.iter().map(|d| d.len()).sum::<usize>()
+    }
+    /// Should be called once per frame, stores a state every `interval` calls
+    pub fn record(&mut self, cpu: &Cpu) {
+        self.frames += 1;
+        if self.frames < self.interval { return }
+        self.frames = 0;
+        self.push(cpu);
+    }
+    /// Unconditionally stores the current state
+    pub fn push(&mut self, cpu: &Cpu) {
+        let mut state = vec![0; cpu.snapshot_size()];
+        if cpu.snapshot(&mut state).is_err() { return }
+        if let Some(head) = self.head.take() {
+            self.deltas.push_back(encode_delta(&state, &head));
+            if self.deltas.len() >= self.capacity {
+                self.deltas.pop_front();
+            }
+        }
+        self.head = Some(state);
+    }
+    /// Drops the most recent state and restores the one before it,
+    /// recording afterwards resumes from there.
+    /// Returns false when the oldest state has been reached.
+    pub fn step_back(&mut self, cpu: &mut Cpu) -> Result<bool, SnapshotError> {
+        let Some(head) = &self.head else { return Ok(false) };
+        let Some(delta) = self.deltas.pop_back() else { return Ok(false) };
+        let prev = apply_delta(head, &delta);
+        if let Err(e) = cpu.restore(&prev) {
+            self.deltas.push_back(delta);
+            return Err(e);
+        }
+        self.head = Some(prev);
+        self.frames = 0;
+        Ok(true)
+    }
+}
+
+/// Encodes `target` relative to `base` as:
+/// target length (u32 LE), then pairs of [zero run][literal run][literal bytes]
+/// with the runs as LEB128 and the literals XORed with the base.
+fn encode_delta(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(target.len() as u32).to_le_bytes());
+    let diff = |i: usize| target[i] ^ base.get(i).copied().unwrap_or(0);
+    let mut i = 0;
+    while i < target.len() {
+        let start = i;
+        while i < target.len() && diff(i) == 0 { i += 1 }
+        let zeros = i - start;
+        let start = i;
+        while i < target.len() && diff(i) != 0 { i += 1 }
+        write_varint(&mut out, zeros);
+        write_varint(&mut out, i - start);
+        out.extend((start..i).map(diff));
+    }
+    out
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> Vec<u8> {
+    let len = u32::from_le_bytes([delta[0], delta[1], delta[2], delta[3]]) as usize;
+    let mut out: Vec<u8> = (0..len).map(|i| base.get(i).copied().unwrap_or(0)).collect();
+    let mut pos = 4;
+    let mut i = 0;
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let literals = read_varint(delta, &mut pos);
+        for b in &delta[pos..pos + literals] {
+            out[i] ^= b;
+            i += 1;
+        }
+        pos += literals;
+    }
+    out
+}
+
+fn write_varint(out: &mut Vec<u8>, mut val: usize) {
+    loop {
+        let byte = (val & 0x7F) as u8;
+        val >>= 7;
+        if val == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut val = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        val |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 { return val }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // counts up in V0 and draws it
+    const ROM: [u8; 10] = [
+        0x70, 0x01,
+        0xf0, 0x29,
+        0xd1, 0x15,
+        0x00, 0xe0,
+        0x12, 0x00
+    ];
+
+    fn snapshot(cpu: &Cpu) -> Vec<u8> {
+        let mut data = vec![0; cpu.snapshot_size()];
+        cpu.snapshot(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn delta_roundtrip() {
+        let base = [1, 2, 3, 4, 5, 6, 7, 8];
+        let target = [1, 2, 9, 4, 5, 6, 0, 8, 10, 11];
+        let delta = encode_delta(&base, &target);
+        assert!(apply_delta(&base, &delta) == target);
+        let delta = encode_delta(&target, &base);
+        assert!(apply_delta(&target, &delta) == base);
+    }
+    #[test]
+    fn delta_is_compact() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        let a = snapshot(&cpu);
+        let _ = cpu.step();
+        let b = snapshot(&cpu);
+        assert!(encode_delta(&b, &a).len() < 16);
+    }
+    #[test]
+    fn step_back_restores_states() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        let mut rewind = Rewind::new(1, 16);
+        let mut states = Vec::new();
+        for _ in 0..5 {
+            for _ in 0..3 {
+                let _ = cpu.step();
+            }
+            rewind.record(&cpu);
+            states.push(snapshot(&cpu));
+        }
+        assert!(rewind.len() == 5);
+        states.pop();
+        while let Some(expected) = states.pop() {
+            assert!(rewind.step_back(&mut cpu) == Ok(true));
+            assert!(snapshot(&cpu) == expected);
+        }
+        assert!(rewind.step_back(&mut cpu) == Ok(false));
+        assert!(rewind.len() == 1);
+    }
+    #[test]
+    fn record_interval() {
+        let cpu = Cpu::new();
+        let mut rewind = Rewind::new(4, 16);
+        for _ in 0..10 {
+            rewind.record(&cpu);
+        }
+        assert!(rewind.len() == 2);
+    }
+    #[test]
+    fn capacity_drops_oldest() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        let mut rewind = Rewind::new(1, 3);
+        for _ in 0..10 {
+            let _ = cpu.step();
+            rewind.record(&cpu);
+        }
+        assert!(rewind.len() == 3);
+        let mut count = 0;
+        while rewind.step_back(&mut cpu) == Ok(true) {
+            count += 1;
+        }
+        assert!(count == 2);
+        // state after the 8th step
+        assert!(cpu.pc == 0x206);
+    }
+    #[test]
+    fn resume_after_rewind() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        let mut rewind = Rewind::new(1, 16);
+        for _ in 0..4 {
+            let _ = cpu.step();
+            rewind.record(&cpu);
+        }
+        let _ = rewind.step_back(&mut cpu);
+        let _ = rewind.step_back(&mut cpu);
+        assert!(cpu.pc == 0x204);
+        let _ = cpu.step();
+        rewind.record(&cpu);
+        let resumed = snapshot(&cpu);
+        let _ = cpu.step();
+        rewind.record(&cpu);
+        assert!(rewind.len() == 4);
+        let _ = rewind.step_back(&mut cpu);
+        assert!(snapshot(&cpu) == resumed);
+    }
+}
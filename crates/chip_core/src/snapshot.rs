@@ -0,0 +1,432 @@
+//! Save-state snapshots of the complete machine.
+//!
+//! A snapshot is a flat little-endian byte string:
+//!
+//! | offset | size | field                                              |
+//! |--------|------|----------------------------------------------------|
+//! | 0      | 4    | magic `C8SS`                                       |
+//! | 4      | 1    | format version                                     |
+//! | 5      | 1    | platform (0 CHIP-8, 1 SCHIP, 2 XO-CHIP)            |
+//! | 6      | 1    | quirks, see `Quirks::to_bits`                      |
+//! | 7      | 1    | flags: hires, redraw, vblank (bits 0 to 2)         |
+//! | 8      | 2    | PC                                                 |
+//! | 10     | 2    | I                                                  |
+//! | 12     | 1    | SP                                                 |
+//! | 13     | 1    | delay timer                                        |
+//! | 14     | 1    | sound timer                                        |
+//! | 15     | 1    | selected planes                                    |
+//! | 16     | 4    | random source state                                |
+//! | 20     | 2    | keys, one bit per key                              |
+//! | 22     | 1    | number of queued key events                        |
+//! | 23     | 1    | key pressed during FX0A, 0x10 + key or 0           |
+//! | 24     | 1    | pitch                                              |
+//! | 25     | 1    | run state, see below                               |
+//! | 26     | 9    | fault of a halted machine, see below               |
+//! | 35     | 4    | VIP cycle budget carried into the next frame (i32) |
+//! | 39     | 16   | V registers                                        |
+//! | 55     | 32   | stack                                              |
+//! | 87     | 16   | RPL flags                                          |
+//! | 103    | 16   | audio pattern                                      |
+//! | 119    | 16   | queued key events, key + 0x10 for a press          |
+//! | 135    | -    | memory, `Cpu::ram_size` bytes for the platform     |
+//! | -      | -    | display planes at the current resolution           |
+//!
+//! The run state is 0 when running, 1 when exited, 2 when halted and
+//! 0x10 + X when FX0A waits for a key into VX.
+//!
+//! The fault is the `ErrorKind` (0 to 6 in declaration order, bit 7 set
+//! when the opcode is known), its operand (4), then the PC (2) and the
+//! opcode (2) of the faulting instruction. It is all zeros unless halted.
+use crate::{
+    cpu::{Cpu, RunState},
+    errors::{ChipError, ErrorKind, SnapshotError},
+    random::RandomSource,
+    globals::{
+        RAM_SIZE, XO_RAM_SIZE, STACK_SIZE, REG_COUNT, RPL_COUNT, AUDIO_PATTERN_SIZE, KEY_QUEUE_SIZE,
+        SCREEN_BUFFER_SIZE, HIRES_SCREEN_BUFFER_SIZE, PLANE_COUNT
+    },
+    input::{KeyQueue, KEY_DOWN},
+    quirks::{Platform, Quirks},
+    utils::{keys_to_bits, keys_from_bits}
+};
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"C8SS";
+pub const SNAPSHOT_VERSION: u8 = 4;
+const HEADER_SIZE: usize = 135;
+const FAULT_SIZE: usize = 9;
+/// set on the fault kind when the opcode is known
+const FAULT_OPCODE: u8 = 0x80;
+
+impl Cpu {
+    /// Number of bytes `snapshot` will write for the current state
+    pub fn snapshot_size(&self) -> usize {
+        HEADER_SIZE + self.ram_size() + PLANE_COUNT * self.display.size()
+    }
+    /// Serializes the machine into `out`, returns the number of bytes written
+    pub fn snapshot(&self, out: &mut [u8]) -> Result<usize, SnapshotError> {
+        let size = self.snapshot_size();
+        if out.len() < size { return Err(SnapshotError::BufferTooSmall(size)) }
+        let mut w = Writer { buf: out, pos: 0 };
+
+        w.bytes(&SNAPSHOT_MAGIC);
+        w.u8(SNAPSHOT_VERSION);
+        w.u8(self.platform as u8);
+        w.u8(self.quirks.to_bits());
+        w.u8(
+            self.display.hires as u8
+                | (self.redraw as u8) << 1
+                | (self.vblank as u8) << 2
+        );
+        w.u16(self.pc);
+        w.u16(self.i);
+        w.u8(self.sp as u8);
+        w.u8(self.delay_timer);
+        w.u8(self.sound_timer);
+        w.u8(self.display.selected);
+        w.u32(self.random.state());
+        w.u16(keys_to_bits(&self.keys));
+        w.u8(self.key_queue.len as u8);
+        w.u8(self.wait_key.map_or(0, |key| KEY_DOWN | key));
+        w.u8(self.pitch);
+        w.u8(match self.state {
+            RunState::Running => 0,
+            RunState::Exited => 1,
+            RunState::Halted(_) => 2,
+            RunState::WaitingForKey(x) => 0x10 | x
+        });
+        w.fault(self.halted());
+        w.u32(self.vip_budget as u32);
+        w.bytes(&self.v);
+        for val in self.stack {
+            w.u16(val);
+        }
+        w.bytes(&self.rpl);
+        w.bytes(&self.audio_pattern);
+        let mut events = [0; KEY_QUEUE_SIZE];
+        for (slot, event) in events.iter_mut().zip(self.key_queue.iter()) {
+            *slot = event;
+        }
+        w.bytes(&events);
+        w.bytes(&self.memory[..self.ram_size()]);
+        let display_size = self.display.size();
+        for plane in self.display.buffers.iter() {
+            w.bytes(&plane[..display_size]);
+        }
+        Ok(w.pos)
+    }
+    /// Restores a state written by `snapshot`.
+    /// The data is fully validated first, on error the machine is left untouched.
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+        let mut r = Reader { buf: data, pos: 0 };
+        if r.bytes(4)? != SNAPSHOT_MAGIC { return Err(SnapshotError::BadMagic) }
+        let version = r.u8()?;
+        if version != SNAPSHOT_VERSION { return Err(SnapshotError::UnsupportedVersion(version)) }
+        let platform = Platform::from_u8(r.u8()?).ok_or(SnapshotError::Corrupted)?;
+        let quirks = Quirks::from_bits(r.u8()?);
+        let flags = r.u8()?;
+        let hires = flags & 1 != 0;
+        let ram_size = match platform {
+            Platform::XoChip => XO_RAM_SIZE,
+            _ => RAM_SIZE
+        };
+        let display_size = if hires { HIRES_SCREEN_BUFFER_SIZE } else { SCREEN_BUFFER_SIZE };
+        if data.len() != HEADER_SIZE + ram_size + PLANE_COUNT * display_size {
+            return Err(SnapshotError::Corrupted);
+        }
+        let pc = r.u16()?;
+        let i = r.u16()?;
+        let sp = r.u8()? as usize;
+        if sp > STACK_SIZE { return Err(SnapshotError::Corrupted) }
+        let delay_timer = r.u8()?;
+        let sound_timer = r.u8()?;
+        let selected = r.u8()?;
+        if selected >> PLANE_COUNT != 0 { return Err(SnapshotError::Corrupted) }
+        let random = r.u32()?;
+        if !self.random.is_valid_state(random) { return Err(SnapshotError::Corrupted) }
+        let keys = keys_from_bits(r.u16()?);
+        let queue_len = r.u8()? as usize;
+        if queue_len > KEY_QUEUE_SIZE { return Err(SnapshotError::Corrupted) }
+        let wait_key = match r.u8()? {
+            0 => None,
+            key if key & !0xF == KEY_DOWN => Some(key & 0xF),
+            _ => return Err(SnapshotError::Corrupted)
+        };
+        let pitch = r.u8()?;
+        let state = r.u8()?;
+        let fault = r.fault()?;
+        let state = match state {
+            0 => RunState::Running,
+            1 => RunState::Exited,
+            2 => RunState::Halted(fault),
+            state if state & 0xF0 == 0x10 => RunState::WaitingForKey(state & 0xF),
+            _ => return Err(SnapshotError::Corrupted)
+        };
+        let vip_budget = r.u32()? as i32;
+
+        self.platform = platform;
+        self.quirks = quirks;
+        self.display.hires = hires;
+        self.redraw = flags & 1 << 1 != 0;
+        self.vblank = flags & 1 << 2 != 0;
+        self.pc = pc;
+        self.i = i;
+        self.sp = sp;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.display.selected = selected;
+        self.random.set_state(random);
+        self.keys = keys;
+        self.wait_key = wait_key;
+        self.pitch = pitch;
+        self.state = state;
+        self.vip_budget = vip_budget;
+        self.v.copy_from_slice(r.bytes(REG_COUNT)?);
+        for val in self.stack.iter_mut() {
+            *val = r.u16()?;
+        }
+        self.rpl.copy_from_slice(r.bytes(RPL_COUNT)?);
+        self.audio_pattern.copy_from_slice(r.bytes(AUDIO_PATTERN_SIZE)?);
+        self.key_queue = KeyQueue { start: 0, len: queue_len, ..Default::default() };
+        self.key_queue.events.copy_from_slice(r.bytes(KEY_QUEUE_SIZE)?);
+        self.memory = [0; XO_RAM_SIZE];
+        self.memory[..ram_size].copy_from_slice(r.bytes(ram_size)?);
+        self.clear_cache();
+        for plane in self.display.buffers.iter_mut() {
+            *plane = [0; HIRES_SCREEN_BUFFER_SIZE];
+            plane[..display_size].copy_from_slice(r.bytes(display_size)?);
+        }
+        Ok(())
+    }
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize
+}
+impl Writer<'_> {
+    fn bytes(&mut self, data: &[u8]) {
+        self.buf[self.pos..self.pos + data.len()].copy_from_slice(data);
+        self.pos += data.len();
+    }
+    fn u8(&mut self, val: u8) {
+        self.bytes(&[val]);
+    }
+    fn u16(&mut self, val: u16) {
+        self.bytes(&val.to_le_bytes());
+    }
+    fn u32(&mut self, val: u32) {
+        self.bytes(&val.to_le_bytes());
+    }
+    fn fault(&mut self, error: Option<&ChipError>) {
+        let Some(error) = error else { return self.bytes(&[0; FAULT_SIZE]) };
+        let (kind, operand) = match error.kind {
+            ErrorKind::IllegalInst(word) => (0, word as u32),
+            ErrorKind::IllegalAddr(addr) => (1, addr as u32),
+            ErrorKind::IllegalReg(reg) => (2, reg as u32),
+            ErrorKind::IllegalKey(key) => (3, key as u32),
+            ErrorKind::StackOverflow => (4, 0),
+            ErrorKind::StackUnderflow => (5, 0),
+            ErrorKind::RomTooLarge(size) => (6, size as u32)
+        };
+        self.u8(kind | if error.opcode.is_some() { FAULT_OPCODE } else { 0 });
+        self.u32(operand);
+        self.u16(error.pc);
+        self.u16(error.opcode.unwrap_or(0));
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize
+}
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        let data = self.buf.get(self.pos..self.pos + len).ok_or(SnapshotError::Corrupted)?;
+        self.pos += len;
+        Ok(data)
+    }
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.bytes(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+    fn u32(&mut self) -> Result<u32, SnapshotError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+    fn fault(&mut self) -> Result<ChipError, SnapshotError> {
+        let kind = self.u8()?;
+        let operand = self.u32()?;
+        let pc = self.u16()?;
+        let opcode = self.u16()?;
+        let opcode = (kind & FAULT_OPCODE != 0).then_some(opcode);
+        let kind = match kind & !FAULT_OPCODE {
+            0 => ErrorKind::IllegalInst(operand as u16),
+            1 => ErrorKind::IllegalAddr(operand as u16),
+            2 => ErrorKind::IllegalReg(operand as u8),
+            3 => ErrorKind::IllegalKey(operand as u8),
+            4 => ErrorKind::StackOverflow,
+            5 => ErrorKind::StackUnderflow,
+            6 => ErrorKind::RomTooLarge(operand as usize),
+            _ => return Err(SnapshotError::Corrupted)
+        };
+        Ok(ChipError { kind, pc, opcode })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Preset;
+
+    const MAX_SIZE: usize = HEADER_SIZE + XO_RAM_SIZE + PLANE_COUNT * HIRES_SCREEN_BUFFER_SIZE;
+
+    // draws random sprites in a loop
+    const ROM: [u8; 12] = [
+        0xc0, 0xff,
+        0xc1, 0x1f,
+        0xa0, 0x50,
+        0xd0, 0x15,
+        0x70, 0x01,
+        0x12, 0x00
+    ];
+
+    #[test]
+    fn header_size() {
+        let cpu = Cpu::new();
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+        assert!(size == cpu.snapshot_size());
+        assert!(size == HEADER_SIZE + RAM_SIZE + PLANE_COUNT * SCREEN_BUFFER_SIZE);
+        assert!(buf[..4] == SNAPSHOT_MAGIC);
+    }
+    #[test]
+    fn restore_is_bit_exact() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &ROM).unwrap();
+        cpu.set_keys([true; 0x10]);
+        for _ in 0..50 {
+            let _ = cpu.step();
+        }
+        let mut saved = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut saved).unwrap();
+
+        for _ in 0..50 {
+            let _ = cpu.step();
+        }
+        let mut expected = [0; MAX_SIZE];
+        cpu.snapshot(&mut expected).unwrap();
+
+        let mut other = Cpu::with_preset(Preset::XoChip);
+        other.restore(&saved[..size]).unwrap();
+        for _ in 0..50 {
+            let _ = other.step();
+        }
+        let mut actual = [0; MAX_SIZE];
+        other.snapshot(&mut actual).unwrap();
+        assert!(actual == expected);
+    }
+    #[test]
+    fn restore_xo_hires() {
+        let mut cpu = Cpu::with_preset(Preset::XoChip);
+        cpu.display.set_hires(true);
+        cpu.memory[0xFFFF] = 0xAB;
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+        assert!(size == MAX_SIZE);
+
+        let mut other = Cpu::new();
+        other.restore(&buf[..size]).unwrap();
+        assert!(other.platform() == Platform::XoChip);
+        assert!(other.get_display_size() == (128, 64));
+        assert!(other.memory[0xFFFF] == 0xAB);
+    }
+    #[test]
+    fn restore_run_state() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0xf7, 0x0a]).unwrap();
+        let _ = cpu.step();
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+        let mut other = Cpu::new();
+        other.restore(&buf[..size]).unwrap();
+        assert!(other.run_state() == &RunState::WaitingForKey(7));
+
+        // a halted machine stays on its fault
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x00, 0xee]).unwrap();
+        let error = cpu.step().unwrap_err();
+        let size = cpu.snapshot(&mut buf).unwrap();
+        let mut other = Cpu::new();
+        other.restore(&buf[..size]).unwrap();
+        assert!(other.halted() == Some(&error));
+        assert!(other.step() == Err(error));
+    }
+    #[test]
+    fn restore_vip_budget() {
+        // 6XNN and 1NNN in a loop, every frame overruns its budget
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x60, 0x01, 0x12, 0x00]).unwrap();
+        cpu.run_vip_frame();
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+
+        let mut other = Cpu::new();
+        other.vip_budget = 100;
+        other.restore(&buf[..size]).unwrap();
+        for _ in 0..10 {
+            assert!(other.run_vip_frame() == cpu.run_vip_frame());
+        }
+        let mut expected = [0; MAX_SIZE];
+        cpu.snapshot(&mut expected).unwrap();
+        let mut actual = [0; MAX_SIZE];
+        other.snapshot(&mut actual).unwrap();
+        assert!(actual == expected);
+    }
+    #[test]
+    fn restore_corrupted_fields() {
+        let cpu = Cpu::new();
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+        let mut other = Cpu::new();
+
+        // a plane that does not exist
+        let mut data = buf;
+        data[15] = 1 << PLANE_COUNT;
+        assert!(other.restore(&data[..size]) == Err(SnapshotError::Corrupted));
+        // xorshift stuck at zero
+        let mut data = buf;
+        data[16..20].fill(0);
+        assert!(other.restore(&data[..size]) == Err(SnapshotError::Corrupted));
+        // FX0A key without its pressed flag
+        let mut data = buf;
+        data[23] = 0x05;
+        assert!(other.restore(&data[..size]) == Err(SnapshotError::Corrupted));
+        assert!(other.restore(&buf[..size]).is_ok());
+    }
+    #[test]
+    fn buffer_too_small() {
+        let cpu = Cpu::new();
+        let mut buf = [0; 16];
+        assert!(cpu.snapshot(&mut buf) == Err(SnapshotError::BufferTooSmall(cpu.snapshot_size())));
+    }
+    #[test]
+    fn restore_invalid() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x234;
+        let mut buf = [0; MAX_SIZE];
+        let size = cpu.snapshot(&mut buf).unwrap();
+
+        let mut other = Cpu::new();
+        assert!(other.restore(&buf[..size - 1]) == Err(SnapshotError::Corrupted));
+        buf[25] = 3;
+        assert!(other.restore(&buf[..size]) == Err(SnapshotError::Corrupted));
+        buf[4] = SNAPSHOT_VERSION + 1;
+        assert!(other.restore(&buf[..size]) == Err(SnapshotError::UnsupportedVersion(SNAPSHOT_VERSION + 1)));
+        buf[0] = 0;
+        assert!(other.restore(&buf[..size]) == Err(SnapshotError::BadMagic));
+        assert!(other.pc == 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! COSMAC VIP timing model.
+//!
+//! The VIP runs the CDP1802 at 1.76 MHz, a machine cycle takes 8 clocks.
+//! That gives 3668 machine cycles per 60 Hz frame, 1024 of which are stolen
+//! by the display DMA (128 scanlines of 8 bytes). The instruction costs are
+//! the machine cycles spent by the original interpreter, including its
+//! fetch and decode loop.
+use crate::{
+    cpu::{Cpu, Frame, RunState},
+    disasm::Instruction,
+    errors::ChipError
+};
+
+pub const VIP_FRAME_CYCLES: i32 = 3668;
+pub const VIP_DISPLAY_CYCLES: i32 = 1024;
+/// fetch and decode overhead of every instruction
+pub const VIP_FETCH_CYCLES: u32 = 40;
+
+impl Cpu {
+    /// Machine cycles the VIP interpreter spends on an instruction.
+    /// `skipped` tells whether a conditional skip was taken.
+    pub fn vip_cycles(&self, inst: Instruction, skipped: bool) -> u32 {
+        let skip = if skipped { 4 } else { 0 };
+        let exec = match inst {
+            Instruction::Cls => 3078,
+            Instruction::Ret => 10,
+            Instruction::Jump(_) => 12,
+            Instruction::Call(_) => 26,
+            Instruction::SkipEqImm(..) | Instruction::SkipNeImm(..) => 10 + skip,
+            Instruction::SkipEq(..) | Instruction::SkipNe(..) => 14 + skip,
+            Instruction::SkipKey(_) | Instruction::SkipNotKey(_) => 14 + skip,
+            Instruction::SetImm(..) => 6,
+            Instruction::AddImm(..) => 10,
+            Instruction::Set(..) | Instruction::Or(..) | Instruction::And(..) | Instruction::Xor(..)
+                | Instruction::Add(..) | Instruction::Sub(..) | Instruction::Shr(..)
+                | Instruction::SubN(..) | Instruction::Shl(..) => 44,
+            Instruction::SetI(_) => 12,
+            Instruction::JumpOffset(nnn) => {
+                // crossing a page costs an extra branch
+                let x = if self.quirks.jump_vx { nnn >> 8 } else { 0 };
+                let target = nnn as u32 + self.v[x as usize & 0xF] as u32;
+                if target >> 8 != nnn as u32 >> 8 { 24 } else { 22 }
+            },
+            Instruction::Random(..) => 36,
+            Instruction::Draw(x, _, n) => {
+                // rows straddling two bytes are shifted and written twice
+                let aligned = self.v[x as usize & 0xF].is_multiple_of(8);
+                68 + n as u32 * if aligned { 46 } else { 70 }
+            },
+            Instruction::GetDelay(_) | Instruction::SetDelay(_) | Instruction::SetSound(_) => 10,
+            Instruction::AddI(_) | Instruction::Font(_) => 16,
+            Instruction::Bcd(x) => {
+                let val = self.v[x as usize & 0xF] as u32;
+                80 + 16 * (val / 100 + val % 100 / 10 + val % 10)
+            },
+            Instruction::Store(x) | Instruction::Load(x) => 14 + 14 * (x as u32 + 1),
+            _ => 0
+        };
+        VIP_FETCH_CYCLES + exec
+    }
+    /// Runs one 60 Hz frame of VIP machine time and ticks the timers.
+    /// An instruction overrunning the frame borrows from the next one,
+    /// a draw waiting for the vertical blank ends the frame early.
+    pub fn run_vip_frame(&mut self) -> Frame {
+        let mut frame = Frame::default();
+        self.vip_budget += VIP_FRAME_CYCLES - VIP_DISPLAY_CYCLES;
+        while self.vip_budget > 0 && self.ready() {
+            match self.vip_step() {
+                Ok(true) => frame.steps += 1,
+                Ok(false) => break,
+                Err(e) => {
+                    frame.error = Some(e);
+                    break;
+                }
+            }
+        }
+        // time left while blocked is spent idling
+        self.vip_budget = self.vip_budget.min(0);
+        self.decrease_timers();
+        self.end_frame(frame)
+    }
+    /// Executes and charges a single instruction, false when stalled on a draw
+    fn vip_step(&mut self) -> Result<bool, ChipError> {
+        if self.state != RunState::Running {
+            // completes a pending FX0A
+            self.step()?;
+            return Ok(true);
+        }
+        // a fetch fault is left to `step` and its error policy
+        let Ok(inst) = self.current_instruction() else {
+            self.step()?;
+            return Ok(true);
+        };
+        let pc = self.pc;
+        // costs depend on the registers before execution
+        let (cost, skip_cost) = (self.vip_cycles(inst, false), self.vip_cycles(inst, true));
+        self.step()?;
+        if self.pc == pc && matches!(inst, Instruction::Draw(..)) {
+            // idle until the display interrupt
+            self.vip_budget = 0;
+            return Ok(false);
+        }
+        let skipped = self.pc == pc.wrapping_add(4) && !matches!(inst, Instruction::LongI);
+        self.vip_budget -= if skipped { skip_cost } else { cost } as i32;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::{Preset, Quirks};
+
+    #[test]
+    fn skip_costs_more() {
+        let cpu = Cpu::new();
+        let inst = Instruction::SkipEqImm(0, 0);
+        assert!(cpu.vip_cycles(inst, true) == cpu.vip_cycles(inst, false) + 4);
+    }
+    #[test]
+    fn draw_cost_depends_on_size_and_alignment() {
+        let mut cpu = Cpu::new();
+        let small = cpu.vip_cycles(Instruction::Draw(0, 1, 1), false);
+        let large = cpu.vip_cycles(Instruction::Draw(0, 1, 15), false);
+        assert!(large > small);
+        cpu.v[0] = 3;
+        assert!(cpu.vip_cycles(Instruction::Draw(0, 1, 15), false) > large);
+    }
+    #[test]
+    fn jump_offset_page_crossing() {
+        // B2F0 crosses into the next page only through V2
+        let mut cpu = Cpu::new();
+        cpu.v[2] = 0x20;
+        let inst = Instruction::JumpOffset(0x2F0);
+        assert!(cpu.vip_cycles(inst, false) == VIP_FETCH_CYCLES + 22);
+        cpu.quirks = Quirks { jump_vx: true, ..Quirks::default() };
+        assert!(cpu.vip_cycles(inst, false) == VIP_FETCH_CYCLES + 24);
+    }
+    #[test]
+    fn cost_before_execution() {
+        // DF01 twice on the same spot, the second draw sets VF
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0xdf, 0x01, 0xdf, 0x01]).unwrap();
+        cpu.i = 0x200;
+        cpu.vip_step().unwrap();
+        assert!(cpu.v[0xF] == 0);
+        cpu.vip_budget = 0;
+        let cost = cpu.vip_cycles(Instruction::Draw(0xF, 0, 1), false);
+        cpu.vip_step().unwrap();
+        assert!(cpu.v[0xF] == 1);
+        assert!(cpu.vip_budget == -(cost as i32));
+    }
+    #[test]
+    fn fetch_fault_halts() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0xfff;
+        assert!(cpu.run_vip_frame().error.is_some());
+        assert!(cpu.halted().is_some());
+        // reported once, not on every frame
+        assert!(cpu.run_vip_frame().error.is_none());
+    }
+    #[test]
+    fn frame_budget() {
+        // 6XNN and 1NNN in a loop: 46 + 52 cycles per iteration
+        let mut cpu = Cpu::new();
+        cpu.load_rom(0x200, &[0x60, 0x01, 0x12, 0x00]).unwrap();
+        assert!(cpu.run_vip_frame().error.is_none());
+        // 2644 cycles: 27 iterations, the last jump overruns by 2
+        assert!(cpu.vip_budget == -2);
+        assert!(cpu.vblank);
+        // the debt is paid from the next frame
+        assert!(cpu.run_vip_frame().error.is_none());
+        assert!(cpu.vip_budget == -4);
+    }
+    #[test]
+    fn draw_waits_for_vblank() {
+        let mut cpu = Cpu::with_preset(Preset::CosmacVip);
+        cpu.load_rom(0x200, &[0xd0, 0x01, 0xd0, 0x01, 0x12, 0x00]).unwrap();
+        assert!(cpu.run_vip_frame().error.is_none());
+        // the first draw waits for the interrupt which ends the frame
+        assert!(cpu.pc == 0x200);
+        assert!(cpu.run_vip_frame().error.is_none());
+        assert!(cpu.pc == 0x202);
+    }
+}
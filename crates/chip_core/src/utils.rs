@@ -1,16 +1,17 @@
 #[inline(always)]
-pub fn u8_from_two(a: u8, b: u8) -> u8 {
-    // assumes u4 inputs, but does not verify
-    a << 4 | b
+pub fn u16_from_two(a: u8, b: u8) -> u16 {
+    (a as u16) << 8 | b as u16
 }
 
-#[inline(always)]
-pub fn u16_from_three(a: u8, b: u8, c: u8) -> u16 {
-    // assumes u4 inputs, but does not verify
-    (a as u16) << 8 | (b as u16) << 4 | (c as u16)
+/// packs the key state into a bitmask, key 0 being the lowest bit
+pub fn keys_to_bits(keys: &[bool; 0x10]) -> u16 {
+    keys.iter().enumerate().fold(0, |acc, (i, k)| acc | (*k as u16) << i)
 }
 
-#[inline(always)]
-pub fn u16_from_two(a: u8, b: u8) -> u16 {
-    (a as u16) << 8 | b as u16
+pub fn keys_from_bits(bits: u16) -> [bool; 0x10] {
+    let mut keys = [false; 0x10];
+    for (i, k) in keys.iter_mut().enumerate() {
+        *k = bits & 1 << i != 0;
+    }
+    keys
 }
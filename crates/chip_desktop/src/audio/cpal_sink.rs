@@ -0,0 +1,42 @@
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    Stream
+};
+
+use super::{AudioSink, SampleQueue};
+
+/// A single output stream for the whole run
+pub struct Device {
+    _stream: Stream,
+    queue: SampleQueue,
+    sample_rate: u32
+}
+impl Device {
+    pub fn open() -> Option<Self> {
+        let host = cpal::default_host();
+        let device = host.default_output_device()?;
+        let config: cpal::StreamConfig = device.default_output_config().ok()?.into();
+        let channels = config.channels as usize;
+        let sample_rate = config.sample_rate.0;
+        let queue = SampleQueue::new(sample_rate);
+        let stream = device.build_output_stream(
+            &config,
+            {
+                let queue = queue.clone();
+                move |data: &mut [f32], _| queue.pop_into(data, channels)
+            },
+            |_| {},
+            None
+        ).ok()?;
+        stream.play().ok()?;
+        Some(Self { _stream: stream, queue, sample_rate })
+    }
+}
+impl AudioSink for Device {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn queue(&mut self, samples: &[f32]) {
+        self.queue.push(samples);
+    }
+}
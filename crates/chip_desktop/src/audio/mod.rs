@@ -0,0 +1,82 @@
+//! Audio output.
+//!
+//! The samples rendered each frame go to a sink: a sound card stream from
+//! the backend enabled by cargo features, a WAV file, or nowhere.
+#[cfg(any(feature = "cpal", feature = "tinyaudio"))]
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex}
+};
+
+#[cfg(feature = "cpal")]
+mod cpal_sink;
+#[cfg(feature = "tinyaudio")]
+mod tinyaudio_sink;
+mod wav;
+
+pub use wav::WavSink;
+
+/// Rate of the sinks that are not tied to a device
+pub const SAMPLE_RATE: u32 = 44100;
+
+pub trait AudioSink {
+    fn sample_rate(&self) -> u32;
+    /// Takes the next mono samples, in [-1, 1]
+    fn queue(&mut self, samples: &[f32]);
+}
+
+/// Discards everything, for headless runs
+pub struct NullSink;
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn queue(&mut self, _samples: &[f32]) {}
+}
+
+/// A stream on the default output of the first enabled backend
+pub fn open_device() -> Option<Box<dyn AudioSink>> {
+    #[cfg(feature = "cpal")]
+    if let Some(device) = cpal_sink::Device::open() {
+        return Some(Box::new(device));
+    }
+    #[cfg(feature = "tinyaudio")]
+    if let Some(device) = tinyaudio_sink::Device::open() {
+        return Some(Box::new(device));
+    }
+    None
+}
+
+/// Samples the stream may lag behind the emulation before the oldest are dropped
+#[cfg(any(feature = "cpal", feature = "tinyaudio"))]
+const MAX_QUEUED_SECONDS: f32 = 0.1;
+
+/// Mono samples handed over to a stream callback
+#[cfg(any(feature = "cpal", feature = "tinyaudio"))]
+#[derive(Clone)]
+struct SampleQueue {
+    samples: Arc<Mutex<VecDeque<f32>>>,
+    max: usize
+}
+#[cfg(any(feature = "cpal", feature = "tinyaudio"))]
+impl SampleQueue {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            samples: Arc::default(),
+            max: (sample_rate as f32 * MAX_QUEUED_SECONDS) as usize
+        }
+    }
+    fn push(&self, samples: &[f32]) {
+        let mut queue = self.samples.lock().unwrap();
+        queue.extend(samples);
+        let excess = queue.len().saturating_sub(self.max);
+        queue.drain(..excess);
+    }
+    /// Fills interleaved frames, with silence on underrun
+    fn pop_into(&self, data: &mut [f32], channels: usize) {
+        let mut queue = self.samples.lock().unwrap();
+        for frame in data.chunks_mut(channels) {
+            frame.fill(queue.pop_front().unwrap_or(0.));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use tinyaudio::prelude::*;
+
+use super::{AudioSink, SampleQueue, SAMPLE_RATE};
+
+/// A single output stream for the whole run
+pub struct Device {
+    _inner: Box<dyn BaseAudioOutputDevice>,
+    queue: SampleQueue,
+    sample_rate: u32
+}
+impl Device {
+    pub fn open() -> Option<Self> {
+        let params = OutputDeviceParameters {
+            channels_count: 2,
+            sample_rate: SAMPLE_RATE as usize,
+            channel_sample_count: 1024
+        };
+        let queue = SampleQueue::new(SAMPLE_RATE);
+        let inner = run_output_device(
+            params,
+            {
+                let queue = queue.clone();
+                move |data| queue.pop_into(data, params.channels_count)
+            }
+        ).ok()?;
+        Some(Self { _inner: inner, queue, sample_rate: SAMPLE_RATE })
+    }
+}
+impl AudioSink for Device {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn queue(&mut self, samples: &[f32]) {
+        self.queue.push(samples);
+    }
+}
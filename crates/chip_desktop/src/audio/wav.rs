@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path
+};
+
+use super::{AudioSink, SAMPLE_RATE};
+
+const HEADER_SIZE: u32 = 44;
+/// WAVE_FORMAT_IEEE_FLOAT, the samples are stored as rendered
+const FORMAT_FLOAT: u16 = 3;
+
+/// Records the samples to a mono 32-bit float WAV file.
+/// The chunk sizes are written when the sink is finished or dropped.
+pub struct WavSink<W: Write + Seek> {
+    out: W,
+    data_size: u32,
+    failed: bool
+}
+impl WavSink<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+impl<W: Write + Seek> WavSink<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        write_header(&mut out, 0)?;
+        Ok(Self { out, data_size: 0, failed: false })
+    }
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.out.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.out, self.data_size)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()
+    }
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.out.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += 4 * samples.len() as u32;
+        Ok(())
+    }
+}
+impl<W: Write + Seek> AudioSink for WavSink<W> {
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn queue(&mut self, samples: &[f32]) {
+        if self.failed { return }
+        if let Err(e) = self.write(samples) {
+            eprintln!("Cannot write WAV: {}", e);
+            self.failed = true;
+        }
+    }
+}
+impl<W: Write + Seek> Drop for WavSink<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            eprintln!("Cannot write WAV: {}", e);
+        }
+    }
+}
+
+fn write_header(out: &mut impl Write, data_size: u32) -> io::Result<()> {
+    out.write_all(b"RIFF")?;
+    out.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&FORMAT_FLOAT.to_le_bytes())?;
+    // mono
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    out.write_all(&(4 * SAMPLE_RATE).to_le_bytes())?;
+    // bytes per frame, bits per sample
+    out.write_all(&4u16.to_le_bytes())?;
+    out.write_all(&32u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data_size.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn records_samples() {
+        let mut data = Vec::new();
+        let mut sink = WavSink::new(Cursor::new(&mut data)).unwrap();
+        sink.queue(&[0.5, -0.25]);
+        sink.queue(&[1.]);
+        drop(sink);
+        assert!(data.len() == HEADER_SIZE as usize + 12);
+        assert!(&data[..4] == b"RIFF" && &data[8..16] == b"WAVEfmt ");
+        assert!(data[4..8] == (HEADER_SIZE - 8 + 12).to_le_bytes());
+        assert!(data[40..44] == 12u32.to_le_bytes());
+        assert!(data[44..48] == 0.5f32.to_le_bytes());
+        assert!(data[52..] == 1f32.to_le_bytes());
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::PathBuf;
+
+use chip_core::Preset;
+
+use crate::{
+    filter::Filter,
+    palette::{Palette, PALETTES}
+};
+
+pub const USAGE: &str = "\
+Usage: chip_desktop <rom.ch8 | rom.8o> [options]
+
+Options:
+  --load-addr <addr>   address the ROM is loaded at [0x200]
+  --ipf <n>            instructions per frame [8]
+  --vip                COSMAC VIP instruction timing instead of --ipf
+  --preset <name>      quirks of vip, chip48, schip or xochip
+  --seed <n>           random seed, drawn from the OS by default
+  --scale <n>          initial window pixels per CHIP-8 pixel [8]
+  --integer            scale by whole pixels only
+  --no-grid            no gaps between the pixels
+  --fullscreen         start in borderless fullscreen
+  --palette <name>     green, amber, lcd or high-contrast [green]
+  --bg <rrggbb>        background colour
+  --fg <rrggbb>        colour of the first plane
+  --fg2 <rrggbb>       colour of the second XO-CHIP plane
+  --blend <rrggbb>     colour where both planes are set
+  --filter <list>      comma separated scale2x, scanlines and crt, in order
+  --record <file>      record the input to a movie
+  --play <file>        play back a movie
+  --wav <file>         write the sound to a WAV file instead of playing it
+  --mute               no sound
+  -h, --help           show this help
+
+Keys:
+  1-4 Q-R A-F Z-V      keypad
+  F1-F4                load a state, with Shift to save it
+  F5                   toggle the pixel grid
+  F6                   toggle integer scaling
+  F7                   next palette
+  F8, F9, F10          toggle scanlines, CRT, Scale2x
+  F11                  toggle fullscreen
+  Backspace            rewind while held";
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub load_addr: u16,
+    pub instructions_per_frame: u32,
+    pub vip_timing: bool,
+    pub preset: Option<Preset>,
+    pub seed: Option<u32>,
+    pub scale: usize,
+    pub integer: bool,
+    pub grid: bool,
+    pub fullscreen: bool,
+    pub palette: Palette,
+    pub filters: Vec<Filter>,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+    pub wav: Option<PathBuf>,
+    pub mute: bool
+}
+
+/// Parses the arguments following the program name
+pub fn parse(args: impl IntoIterator<Item=String>) -> Result<Options, String> {
+    let mut rom = None;
+    let mut options = Options {
+        rom: PathBuf::new(),
+        load_addr: 0x200,
+        instructions_per_frame: 8,
+        vip_timing: false,
+        preset: None,
+        seed: None,
+        scale: 8,
+        integer: false,
+        grid: true,
+        fullscreen: false,
+        palette: Palette::default(),
+        filters: Vec::new(),
+        record: None,
+        play: None,
+        wav: None,
+        mute: false
+    };
+    // colours given one by one override the palette, whatever the order
+    let mut colors = [None; 4];
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+        match arg.as_str() {
+            "--load-addr" => options.load_addr = number(&value()?)?,
+            "--ipf" => options.instructions_per_frame = number(&value()?)?,
+            "--vip" => options.vip_timing = true,
+            "--preset" => options.preset = Some(preset(&value()?)?),
+            "--seed" => options.seed = Some(number(&value()?)?),
+            "--scale" => options.scale = number(&value()?)?,
+            "--integer" => options.integer = true,
+            "--no-grid" => options.grid = false,
+            "--fullscreen" => options.fullscreen = true,
+            "--palette" => options.palette = palette(&value()?)?,
+            "--bg" => colors[0] = Some(color(&value()?)?),
+            "--fg" => colors[1] = Some(color(&value()?)?),
+            "--fg2" => colors[2] = Some(color(&value()?)?),
+            "--blend" => colors[3] = Some(color(&value()?)?),
+            "--filter" => options.filters = filters(&value()?)?,
+            "--record" => options.record = Some(PathBuf::from(value()?)),
+            "--play" => options.play = Some(PathBuf::from(value()?)),
+            "--wav" => options.wav = Some(PathBuf::from(value()?)),
+            "--mute" => options.mute = true,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            _ if rom.is_some() => return Err(format!("unexpected argument {}", arg)),
+            _ => rom = Some(PathBuf::from(arg))
+        }
+    }
+    options.rom = rom.ok_or("no ROM given")?;
+    for (dst, color) in options.palette.colors.iter_mut().zip(colors) {
+        *dst = color.unwrap_or(*dst);
+    }
+    if options.scale == 0 { return Err("the scale must be at least 1".to_string()) }
+    if options.instructions_per_frame == 0 { return Err("--ipf must be at least 1".to_string()) }
+    if options.record.is_some() && options.play.is_some() {
+        return Err("--record and --play cannot be combined".to_string());
+    }
+    Ok(options)
+}
+
+pub fn wants_help(args: &[String]) -> bool {
+    args.iter().any(|arg| arg == "-h" || arg == "--help")
+}
+
+/// Decimal, or hexadecimal with a 0x prefix
+fn number<T: TryFrom<u64>>(val: &str) -> Result<T, String> {
+    let parsed = match val.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => val.parse()
+    };
+    parsed.ok().and_then(|n| T::try_from(n).ok()).ok_or(format!("invalid number {}", val))
+}
+
+fn color(val: &str) -> Result<u32, String> {
+    let hex = val.strip_prefix('#').unwrap_or(val);
+    if hex.len() != 6 { return Err(format!("invalid colour {}, expected rrggbb", val)) }
+    u32::from_str_radix(hex, 16).map_err(|_| format!("invalid colour {}, expected rrggbb", val))
+}
+
+fn palette(val: &str) -> Result<Palette, String> {
+    Palette::named(val).ok_or_else(|| {
+        let names: Vec<&str> = PALETTES.iter().map(|(name, _)| *name).collect();
+        format!("unknown palette {}, expected {}", val, names.join(", "))
+    })
+}
+
+fn filters(val: &str) -> Result<Vec<Filter>, String> {
+    val.split(',').map(|name| {
+        Filter::ALL.into_iter().find(|f| f.name() == name)
+            .ok_or(format!("unknown filter {}, expected scale2x, scanlines or crt", name))
+    }).collect()
+}
+
+fn preset(val: &str) -> Result<Preset, String> {
+    match val {
+        "vip" => Ok(Preset::CosmacVip),
+        "chip48" => Ok(Preset::Chip48),
+        "schip" => Ok(Preset::SuperChip),
+        "xochip" => Ok(Preset::XoChip),
+        _ => Err(format!("unknown preset {}, expected vip, chip48, schip or xochip", val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn options() {
+        let options = parse(args("--fg #ffb000 --ipf 12 game.ch8 --load-addr 0x600 --preset schip --seed 7 --palette lcd")).unwrap();
+        assert!(options.rom == Path::new("game.ch8"));
+        assert!(options.instructions_per_frame == 12);
+        assert!(options.load_addr == 0x600);
+        assert!(options.preset == Some(Preset::SuperChip));
+        assert!(options.seed == Some(7));
+        let lcd = Palette::named("lcd").unwrap().colors;
+        assert!(options.palette.colors == [lcd[0], 0xffb000, lcd[2], lcd[3]]);
+        assert!(options.filters.is_empty());
+        assert!(options.scale == 8);
+        assert!(options.grid && !options.integer);
+        let options = parse(args("game.ch8 --integer --no-grid --fullscreen --filter scale2x,crt")).unwrap();
+        assert!(options.integer && !options.grid && options.fullscreen);
+        assert!(options.filters == [Filter::Scale2x, Filter::Crt]);
+    }
+    #[test]
+    fn errors() {
+        assert!(parse(args("")).err() == Some("no ROM given".to_string()));
+        assert!(parse(args("a.ch8 --fast")).err() == Some("unknown option --fast".to_string()));
+        assert!(parse(args("a.ch8 b.ch8")).is_err());
+        assert!(parse(args("a.ch8 --ipf")).err() == Some("--ipf expects a value".to_string()));
+        assert!(parse(args("a.ch8 --load-addr 0x10000")).err() == Some("invalid number 0x10000".to_string()));
+        assert!(parse(args("a.ch8 --preset foo")).is_err());
+        assert!(parse(args("a.ch8 --bg red")).is_err());
+        assert!(parse(args("a.ch8 --palette blue")).is_err());
+        assert!(parse(args("a.ch8 --filter crt,blur")).err() == Some("unknown filter blur, expected scale2x, scanlines or crt".to_string()));
+        assert!(parse(args("a.ch8 --scale 0")).is_err());
+        assert!(parse(args("a.ch8 --ipf 0")).err() == Some("--ipf must be at least 1".to_string()));
+    }
+}
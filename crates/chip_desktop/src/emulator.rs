@@ -0,0 +1,158 @@
+//! The emulation thread.
+//!
+//! The `Cpu` runs on its own fixed timestep, away from the event loop, so
+//! window drags and slow presents do not stall it. Input arrives through a
+//! channel and each completed frame goes back through a triple buffer.
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant}
+};
+
+use chip_core::{globals::PLANE_COUNT, rewind::Rewind, Cpu, RunState};
+
+use crate::{
+    audio::AudioSink,
+    saves,
+    scheduler::Scheduler,
+    session::Session,
+    triple::Writer
+};
+
+pub const FRAME_SECONDS: f32 = 1. / 60.;
+
+// record every frame so rewinding goes back one frame at a time,
+// keeping 30 seconds of history
+const REWIND_INTERVAL: usize = 1;
+const REWIND_CAPACITY: usize = 30 * 60 / REWIND_INTERVAL;
+
+/// Events sent from the UI thread
+pub enum Input {
+    Key { key: u8, pressed: bool },
+    /// run backwards while held
+    Rewind(bool),
+    Save(u8),
+    Load(u8),
+    Quit
+}
+
+/// Everything the UI needs to present a frame
+#[derive(Clone, Default)]
+pub struct FrameView {
+    pub width: usize,
+    pub height: usize,
+    pub planes: [Vec<u8>; PLANE_COUNT],
+    pub state: RunState,
+    pub sound: bool
+}
+impl FrameView {
+    fn update(&mut self, cpu: &Cpu, sound: bool) {
+        (self.width, self.height) = cpu.get_display_size();
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            plane.clear();
+            plane.extend_from_slice(cpu.get_plane_buffer(i).unwrap_or_default());
+        }
+        self.state = cpu.run_state().clone();
+        self.sound = sound;
+    }
+}
+
+pub struct Emulator {
+    pub cpu: Cpu,
+    pub session: Session,
+    pub vip_timing: bool,
+    pub instructions_per_frame: u32
+}
+impl Emulator {
+    /// Starts the thread, `open_audio` is called on it as some streams cannot move
+    /// between threads. `wake` tells the UI that a new frame was published.
+    pub fn spawn(
+        self,
+        inputs: Receiver<Input>,
+        mut frames: Writer<FrameView>,
+        open_audio: impl FnOnce() -> Box<dyn AudioSink> + Send + 'static,
+        wake: impl Fn() + Send + 'static
+    ) -> JoinHandle<()> {
+        let Emulator { mut cpu, mut session, vip_timing, instructions_per_frame } = self;
+        thread::spawn(move || {
+            let mut audio = open_audio();
+            let mut samples = Vec::new();
+            let mut keys = [false; 0x10];
+            let mut rewind = Rewind::new(REWIND_INTERVAL, REWIND_CAPACITY);
+            let mut rewinding = false;
+            let mut scheduler = Scheduler::new(Duration::from_secs_f32(FRAME_SECONDS), Instant::now());
+            frames.back().update(&cpu, false);
+            frames.publish();
+            wake();
+
+            loop {
+                // sleep until the next frame, waking up early for input
+                let timeout = scheduler.deadline().saturating_duration_since(Instant::now());
+                let input = match inputs.recv_timeout(timeout) {
+                    Ok(input) => Some(input),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => Some(Input::Quit)
+                };
+                match input {
+                    Some(Input::Key { key, pressed }) => {
+                        keys[key as usize] = pressed;
+                        // live input goes straight to the key queue
+                        if session.is_live() {
+                            let _ = if pressed { cpu.key_down(key) } else { cpu.key_up(key) };
+                        }
+                    },
+                    Some(Input::Rewind(held)) => rewinding = held && session.is_live(),
+                    Some(Input::Save(slot)) if session.is_live() => match saves::save(&cpu, slot) {
+                        Ok(_) => println!("Saved slot {}", slot),
+                        Err(e) => eprintln!("Slot {}: {}", slot, e)
+                    },
+                    Some(Input::Load(slot)) if session.is_live() => match saves::load(&mut cpu, slot) {
+                        Ok(_) => println!("Loaded slot {}", slot),
+                        Err(e) => eprintln!("Slot {}: {}", slot, e)
+                    },
+                    Some(Input::Quit) => break,
+                    Some(_) | None => ()
+                }
+
+                let due = scheduler.due(Instant::now());
+                let mut sound = false;
+                for _ in 0..due {
+                    if rewinding {
+                        // one recorded state per frame
+                        if let Err(e) = rewind.step_back(&mut cpu) {
+                            eprintln!("{:?}", e);
+                        }
+                        continue;
+                    }
+                    if !session.is_live() {
+                        // movies latch the input once per frame
+                        cpu.set_keys(session.frame_keys(keys));
+                    }
+                    let frame = if vip_timing {
+                        cpu.run_vip_frame()
+                    } else {
+                        cpu.run_frame(instructions_per_frame)
+                    };
+                    if let Some(e) = &frame.error {
+                        eprintln!("{}", e);
+                    }
+                    rewind.record(&cpu);
+                    sound = frame.sound;
+
+                    samples.resize((audio.sample_rate() as f32 * FRAME_SECONDS) as usize, 0.);
+                    cpu.fill_audio(&mut samples, audio.sample_rate());
+                    audio.queue(&samples);
+                }
+                if due > 0 {
+                    frames.back().update(&cpu, sound);
+                    frames.publish();
+                    wake();
+                }
+            }
+
+            if let Err(e) = session.finish() {
+                eprintln!("Cannot save movie: {}", e);
+            }
+        })
+    }
+}
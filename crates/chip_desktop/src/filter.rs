@@ -0,0 +1,247 @@
+//! Software post-processing.
+//!
+//! Pixel-art filters enlarge the display image before it is scaled to the
+//! window, screen filters then work on the scaled picture. Everything runs
+//! on the CPU, one `0RGB` u32 per pixel as `softbuffer` expects.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// EPX doubling, smoothing the diagonals of the pixel art
+    Scale2x,
+    /// darkens every other line
+    Scanlines,
+    /// tube curvature with a glow around the lit pixels
+    Crt
+}
+impl Filter {
+    pub const ALL: [Filter; 3] = [Filter::Scale2x, Filter::Scanlines, Filter::Crt];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::Scale2x => "scale2x",
+            Filter::Scanlines => "scanlines",
+            Filter::Crt => "crt"
+        }
+    }
+    pub fn is_pixel_art(&self) -> bool {
+        matches!(self, Filter::Scale2x)
+    }
+}
+
+/// Brightness kept on the dark scanlines, in 256ths
+const SCANLINE_LEVEL: u32 = 160;
+/// Barrel distortion at the edges of the tube
+const CURVATURE: f32 = 0.06;
+/// Share of the blurred picture added back as glow, in 256ths
+const BLOOM_LEVEL: u32 = 96;
+/// The glow is computed at a lower resolution, being blurry anyway
+const GLOW_SCALE: usize = 4;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>
+}
+
+/// EPX: each pixel becomes four, taking the colour of a pair of equal
+/// neighbours on a corner unless that would thicken a line
+pub fn scale2x(src: &Image, dst: &mut Image) {
+    let (w, h) = (src.width, src.height);
+    dst.width = 2 * w;
+    dst.height = 2 * h;
+    dst.pixels.resize(4 * w * h, 0);
+    let at = |x: usize, y: usize| src.pixels[y * w + x];
+    for y in 0..h {
+        for x in 0..w {
+            let p = at(x, y);
+            let a = at(x, y.saturating_sub(1));
+            let b = at((x + 1).min(w - 1), y);
+            let c = at(x.saturating_sub(1), y);
+            let d = at(x, (y + 1).min(h - 1));
+            let top = 2 * y * dst.width + 2 * x;
+            let bottom = top + dst.width;
+            dst.pixels[top] = if c == a && c != d && a != b { a } else { p };
+            dst.pixels[top + 1] = if a == b && a != c && b != d { b } else { p };
+            dst.pixels[bottom] = if d == c && d != b && c != a { c } else { p };
+            dst.pixels[bottom + 1] = if b == d && b != a && d != c { d } else { p };
+        }
+    }
+}
+
+/// Darkens the odd lines of a `width` x `height` area of `out`
+pub fn scanlines(out: &mut [u32], stride: usize, width: usize, height: usize) {
+    for row in out.chunks_mut(stride).take(height).skip(1).step_by(2) {
+        for pixel in &mut row[..width] {
+            *pixel = scale(*pixel, SCANLINE_LEVEL);
+        }
+    }
+}
+
+/// Adds a glow and bends the area of `out` like a tube, `scratch` holds a copy
+pub fn crt(out: &mut [u32], stride: usize, width: usize, height: usize, scratch: &mut Vec<u32>) {
+    if width < 2 * GLOW_SCALE || height < 2 * GLOW_SCALE { return }
+    let (glow_width, glow_height) = (width / GLOW_SCALE, height / GLOW_SCALE);
+    let glow = downsample(out, stride, width, height);
+    let glow = blur(&glow, glow_width, glow_height, (glow_width / 40).max(1));
+    // the glow is added before bending, its blur hides the difference
+    scratch.clear();
+    for (y, row) in out.chunks(stride).take(height).enumerate() {
+        let glow = &glow[(y / GLOW_SCALE).min(glow_height - 1) * glow_width..];
+        scratch.extend(row[..width].iter().enumerate().map(|(x, &pixel)| {
+            add(pixel, scale(glow[(x / GLOW_SCALE).min(glow_width - 1)], BLOOM_LEVEL))
+        }));
+    }
+
+    // offsets from the centre in half pixels, and the bulge they cause on
+    // the other axis in 16.16 fixed point
+    let axis = |len: usize| -> Vec<(i64, i64)> {
+        (0..len as i64).map(|i| {
+            let offset = 2 * i - (len as i64 - 1);
+            let n = offset as f32 / (len - 1) as f32;
+            (offset, ((1. + CURVATURE * n * n) * 65536.) as i64)
+        }).collect()
+    };
+    let (columns, rows) = (axis(width), axis(height));
+    let (w, h) = (width as i64, height as i64);
+    for (y, &(dy, bulge_y)) in rows.iter().enumerate() {
+        let line = &mut out[y * stride..y * stride + width];
+        for (pixel, &(dx, bulge_x)) in line.iter_mut().zip(&columns) {
+            let sx = (((dx * bulge_y) >> 16) + w - 1) >> 1;
+            let sy = (((dy * bulge_x) >> 16) + h - 1) >> 1;
+            *pixel = if sx < 0 || sy < 0 || sx >= w || sy >= h {
+                0
+            } else {
+                scratch[sy as usize * width + sx as usize]
+            };
+        }
+    }
+}
+
+/// Averages blocks of `GLOW_SCALE` x `GLOW_SCALE` pixels of a `width` x
+/// `height` area, leaving out the partial blocks on the edges
+fn downsample(pixels: &[u32], stride: usize, width: usize, height: usize) -> Vec<u32> {
+    let (out_width, out_height) = (width / GLOW_SCALE, height / GLOW_SCALE);
+    // red and blue summed side by side, a block cannot overflow their 16 bits
+    let mut sums = vec![(0u32, 0u32); out_width * out_height];
+    for (y, row) in pixels.chunks(stride).take(out_height * GLOW_SCALE).enumerate() {
+        let sums = &mut sums[y / GLOW_SCALE * out_width..];
+        for (sum, block) in sums.iter_mut().zip(row[..out_width * GLOW_SCALE].chunks(GLOW_SCALE)) {
+            for &pixel in block {
+                sum.0 += pixel & 0xff00ff;
+                sum.1 += pixel & 0x00ff00;
+            }
+        }
+    }
+    let count = (GLOW_SCALE * GLOW_SCALE) as u32;
+    sums.iter().map(|&(rb, g)| {
+        (((rb >> 16) / count) << 16) | ((rb & 0xffff) / count) | ((g / count) & 0x00ff00)
+    }).collect()
+}
+
+/// Box blur, horizontal then vertical
+fn blur(pixels: &[u32], width: usize, height: usize, radius: usize) -> Vec<u32> {
+    let mut rows = vec![0; pixels.len()];
+    for y in 0..height {
+        blur_line(&pixels[y * width..], 1, &mut rows[y * width..], width, radius);
+    }
+    let mut out = vec![0; pixels.len()];
+    for x in 0..width {
+        blur_line(&rows[x..], width, &mut out[x..], height, radius);
+    }
+    out
+}
+
+/// Averages each of `len` pixels `step` apart with its `radius` neighbours
+fn blur_line(src: &[u32], step: usize, dst: &mut [u32], len: usize, radius: usize) {
+    let mut sums = [0u32; 3];
+    let channel = |pixel: u32, c: usize| pixel >> (8 * c) & 0xff;
+    for i in 0..radius.min(len) {
+        for (c, sum) in sums.iter_mut().enumerate() {
+            *sum += channel(src[i * step], c);
+        }
+    }
+    for i in 0..len {
+        if i + radius < len {
+            for (c, sum) in sums.iter_mut().enumerate() {
+                *sum += channel(src[(i + radius) * step], c);
+            }
+        }
+        if i > radius {
+            for (c, sum) in sums.iter_mut().enumerate() {
+                *sum -= channel(src[(i - radius - 1) * step], c);
+            }
+        }
+        let count = (i + radius).min(len - 1) + 1 - i.saturating_sub(radius);
+        dst[i * step] = sums.iter().enumerate().map(|(c, sum)| (sum / count as u32) << (8 * c)).sum();
+    }
+}
+
+/// Multiplies each channel by `level` / 256
+fn scale(pixel: u32, level: u32) -> u32 {
+    let rb = ((pixel & 0xff00ff) * level) >> 8 & 0xff00ff;
+    let g = ((pixel & 0x00ff00) * level) >> 8 & 0x00ff00;
+    rb | g
+}
+
+/// Adds each channel, saturating
+fn add(a: u32, b: u32) -> u32 {
+    let rb = (a & 0xff00ff) + (b & 0xff00ff);
+    let g = (a & 0x00ff00) + (b & 0x00ff00);
+    // carries out of each channel turn into a full channel
+    let rb = rb | (((rb >> 8) & 0x010001) * 0xff);
+    let g = g | (((g >> 8) & 0x000100) * 0xff);
+    (rb & 0xff00ff) | (g & 0x00ff00)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale2x_thins_diagonals() {
+        // a diagonal of two pixels stays a one pixel wide line
+        let src = Image { width: 2, height: 2, pixels: vec![1, 0, 0, 1] };
+        let mut dst = Image::default();
+        scale2x(&src, &mut dst);
+        assert!(dst.width == 4 && dst.height == 4);
+        assert!(dst.pixels == [
+            1, 1, 0, 0,
+            1, 0, 1, 0,
+            0, 1, 0, 1,
+            0, 0, 1, 1
+        ]);
+        // flat areas are plain doubled
+        let src = Image { width: 1, height: 1, pixels: vec![5] };
+        scale2x(&src, &mut dst);
+        assert!(dst.pixels == [5; 4]);
+    }
+    #[test]
+    fn scanlines_darken_odd_lines() {
+        let mut out = vec![0xffffff; 3 * 4];
+        scanlines(&mut out, 3, 2, 4);
+        assert!(out[0..3] == [0xffffff; 3]);
+        assert!(out[3..5] == [scale(0xffffff, SCANLINE_LEVEL); 2] && out[5] == 0xffffff);
+        assert!(out[9] == scale(0xffffff, SCANLINE_LEVEL));
+    }
+    #[test]
+    fn crt_bends_corners() {
+        let (width, height) = (64, 32);
+        let mut out = vec![0x404040; width * height];
+        crt(&mut out, width, width, height, &mut Vec::new());
+        assert!(out[0] == 0 && out[width * height - 1] == 0);
+        // the glow brightens a uniform area
+        assert!(out[height / 2 * width + width / 2] > 0x404040);
+    }
+    #[test]
+    fn channels() {
+        assert!(scale(0x80ff40, 128) == 0x407f20);
+        assert!(add(0xf01010, 0x20f001) == 0xffff11);
+        let line = [0, 0, 0x0000ff, 0, 0];
+        let mut out = [0; 5];
+        blur_line(&line, 1, &mut out, 5, 1);
+        assert!(out == [0, 0x55, 0x55, 0x55, 0]);
+        let block = [0x0000ff, 0x0000ff, 0, 0, 0, 0, 0, 0].repeat(4);
+        assert!(downsample(&block, 8, 8, 4) == [0x7f, 0]);
+    }
+}
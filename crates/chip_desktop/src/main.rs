@@ -1,153 +1,289 @@
 use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
     num::NonZeroU32,
-    rc::Rc
+    process::ExitCode,
+    rc::Rc,
+    sync::mpsc
 };
 use winit::{
     event::{Event, WindowEvent, KeyEvent},
     dpi::PhysicalSize,
-    event_loop::{EventLoop, ControlFlow},
+    event_loop::{EventLoopBuilder, ControlFlow},
     keyboard::KeyCode,
-    window::WindowBuilder
+    window::{Fullscreen, WindowBuilder}
 };
 
 use chip_core::{
     Cpu,
-    globals::{SCREEN_WIDTH, SCREEN_HEIGHT}
+    RunState,
+    globals::{SCREEN_WIDTH, SCREEN_HEIGHT},
+    movie::Movie
 };
 
 mod audio;
+mod cli;
+mod emulator;
+mod filter;
+mod palette;
+mod render;
+mod rom;
+mod saves;
+mod scheduler;
+mod session;
+mod triple;
 
-const SCALING: usize = 8;
-const W: usize = SCALING * SCREEN_WIDTH;
-const H: usize = SCALING * SCREEN_HEIGHT;
-const GAP_V: usize = 2;
-const GAP_H: usize = 2;
+use audio::{AudioSink, NullSink, WavSink};
+use emulator::{Emulator, FrameView, Input};
+use filter::Filter;
+use palette::PALETTES;
+use render::{RenderOptions, Renderer};
 
-const STEP_DELAY_SECONDS: f32 = 1. / 480.;
-const TIMER_FACTOR: usize = 8;
-
-fn main() {
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::wants_help(&args) {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match cli::parse(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
     println!("CHIP-8");
-    let mut audio_device = audio::get_device();
-    if let Some(_) = &mut audio_device {
-        println!("Got Audio Device");
+
+    let rom = match rom::load(&options.rom) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Cannot load ROM: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut cpu = match options.preset {
+        Some(preset) => {
+            println!("Preset: {}", preset.name());
+            Cpu::with_preset(preset)
+        },
+        None => Cpu::new()
+    };
+    cpu.set_random_seed(options.seed.unwrap_or_else(entropy_seed));
+    if let Err(e) = cpu.load_rom(options.load_addr, &rom) {
+        eprintln!("Cannot load ROM: {}", e);
+        return ExitCode::FAILURE;
     }
 
-    let rom = include_bytes!("../../../.local/brix.ch8");
+    // --record <file> logs the input, --play <file> replays it
+    let mut vip_timing = options.vip_timing;
+    let mut instructions_per_frame = options.instructions_per_frame;
+    let mut session = session::Session::Live;
+    if let Some(path) = options.record {
+        // movies store the steps in 16 bits, 0 meaning VIP timing
+        let steps = match u16::try_from(instructions_per_frame) {
+            _ if vip_timing => 0,
+            Ok(steps) if steps > 0 => steps,
+            _ => {
+                eprintln!("Cannot record at {} instructions per frame, movies allow 1 to {}", instructions_per_frame, u16::MAX);
+                return ExitCode::FAILURE;
+            }
+        };
+        let movie = Movie::new(&rom, &cpu, cpu.random_seed(), options.load_addr, steps);
+        session = session::Session::record(movie, path);
+    } else if let Some(path) = options.play {
+        match session::Session::play(path, &rom) {
+            Ok((s, c)) => (session, cpu) = (s, c),
+            Err(e) => {
+                eprintln!("Cannot play movie: {}", e);
+                return ExitCode::FAILURE;
+            }
+        }
+        let steps = session.steps_per_frame().unwrap_or_default();
+        vip_timing = steps == 0;
+        instructions_per_frame = steps as u32;
+    }
+    println!("Random seed: {:#010x}", cpu.random_seed());
 
-    let mut cpu = Cpu::new();
-    cpu.load_rom(0x200, rom);
+    // --wav <file> records the sound instead of playing it
+    let wav = match options.wav.map(|path| WavSink::create(&path)).transpose() {
+        Ok(wav) => wav,
+        Err(e) => {
+            eprintln!("Cannot create WAV file: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mute = options.mute;
+    let open_audio = move || -> Box<dyn AudioSink> {
+        match wav {
+            Some(sink) => Box::new(sink),
+            None if mute => Box::new(NullSink),
+            None => audio::open_device().unwrap_or_else(|| {
+                println!("No audio device");
+                Box::new(NullSink)
+            })
+        }
+    };
 
-    
-    // let mut buffer = [0u32; W * H];
+    let scale = options.scale;
+    let mut renderer = Renderer::new(RenderOptions {
+        palette: options.palette,
+        integer: options.integer,
+        grid: options.grid,
+        filters: options.filters
+    });
+    let mut palette_index = PALETTES.iter().position(|(_, p)| *p == options.palette);
 
-    let event_loop = EventLoop::new().unwrap();
+    let event_loop = EventLoopBuilder::new().build().unwrap();
     let window = Rc::new(
         WindowBuilder::new().with_inner_size(
-            PhysicalSize::new(W as u32, H as u32)
+            PhysicalSize::new((scale * SCREEN_WIDTH) as u32, (scale * SCREEN_HEIGHT) as u32)
         )
-        .with_resizable(false)
+        .with_title(title(&RunState::Running))
+        .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)))
         .build(&event_loop).unwrap()
     );
     let context = softbuffer::Context::new(window.clone()).unwrap();
     let mut surface = softbuffer::Surface::new(&context, window.clone()).unwrap();
-    
-    event_loop.set_control_flow(ControlFlow::Poll);
-    
-    let mut keys = [false; 0x10];
-    let mut start = std::time::Instant::now();
-    let mut timer = 0;
 
-    event_loop.run(move |event, elwt| {
-            match event {
-                Event::WindowEvent { window_id, event: WindowEvent::Resized(size) } => {
-                    let _ = surface.resize(
-                        NonZeroU32::new(size.width).unwrap(),
-                        NonZeroU32::new(size.height).unwrap(),
-                    );
-                },
-                Event::WindowEvent { window_id, event: WindowEvent::RedrawRequested } => {
-                    if start.elapsed().as_secs_f32() >= STEP_DELAY_SECONDS {
-                        cpu.set_keys(keys);
-                        
-                        if let Err(e) = cpu.step() {
-                            println!("{:?}", e);
-                        }
-                        let mut buffer = surface.buffer_mut().unwrap();
-                        if cpu.take_redraw() {
-                            // println!("{:?}", cpu.v[0xf]);
-                            // let start = std::time::Instant::now();
-                            read_buffer(&mut buffer, &cpu);
-                            // println!("Redraw {}", start.elapsed().as_secs_f32());
-                        }
+    // the emulator wakes the event loop up when it publishes a frame
+    let (inputs, receiver) = mpsc::channel();
+    let (writer, mut frames) = triple::triple_buffer(FrameView::default());
+    let proxy = event_loop.create_proxy();
+    let emulator = Emulator { cpu, session, vip_timing, instructions_per_frame };
+    let mut thread = Some(emulator.spawn(receiver, writer, open_audio, move || {
+        let _ = proxy.send_event(());
+    }));
 
-                        timer += 1;
-                        if timer > TIMER_FACTOR {
-                            // update timers and buffer at 60Hz
-                            cpu.decrease_timers();
-                            timer = 0;
-                            buffer.present().unwrap();
+    event_loop.set_control_flow(ControlFlow::Wait);
+    let mut shift = false;
+    let mut state = RunState::Running;
+    let mut surface_size = (0, 0);
 
-                            if let Some(device) = &mut audio_device {
-                                if cpu.beeps() { device.beep() } else { device.stop() }
-                            }
+    event_loop.run(move |event, elwt| {
+            match event {
+                Event::WindowEvent { event: WindowEvent::Resized(size), .. } => {
+                    // minimised windows have no surface
+                    if let (Some(width), Some(height)) = (NonZeroU32::new(size.width), NonZeroU32::new(size.height)) {
+                        if surface.resize(width, height).is_ok() {
+                            surface_size = (size.width as usize, size.height as usize);
                         }
-                        // println!("{} {}", 1. / start.elapsed().as_secs_f32(), start.elapsed().as_secs_f32());
-                        start = std::time::Instant::now();
+                        window.request_redraw();
+                    }
+                },
+                Event::UserEvent(()) => window.request_redraw(),
+                Event::WindowEvent { event: WindowEvent::RedrawRequested, .. } => {
+                    let (view, _) = frames.read();
+                    if view.state != state {
+                        window.set_title(&title(&view.state));
+                        state = view.state.clone();
                     }
+                    // drawn at the size of the surface, the window may be ahead of it
+                    let (width, height) = surface_size;
+                    if width == 0 { return }
+                    let mut buffer = surface.buffer_mut().unwrap();
+                    renderer.draw(&mut buffer, width, height, view);
+                    buffer.present().unwrap();
                 },
-                Event::WindowEvent { window_id, event: WindowEvent::KeyboardInput { event, .. } } => {
-                    let KeyEvent { physical_key, state, .. } = event;
+                Event::WindowEvent { event: WindowEvent::ModifiersChanged(modifiers), .. } => {
+                    shift = modifiers.state().shift_key();
+                },
+                Event::WindowEvent { event: WindowEvent::KeyboardInput { event, .. }, .. } => {
+                    let KeyEvent { physical_key, state, repeat, .. } = event;
                     if let winit::keyboard::PhysicalKey::Code(code) = physical_key {
+                        if state.is_pressed() && !repeat {
+                            if let Some(slot) = save_slot(code) {
+                                // Shift + F1-F4 saves, F1-F4 loads
+                                let _ = inputs.send(if shift { Input::Save(slot) } else { Input::Load(slot) });
+                            }
+                            match code {
+                                KeyCode::F5 => renderer.options.grid = !renderer.options.grid,
+                                KeyCode::F6 => renderer.options.integer = !renderer.options.integer,
+                                KeyCode::F7 => {
+                                    let i = palette_index.map_or(0, |i| (i + 1) % PALETTES.len());
+                                    let (name, palette) = PALETTES[i];
+                                    println!("Palette: {}", name);
+                                    renderer.options.palette = palette;
+                                    palette_index = Some(i);
+                                },
+                                KeyCode::F8 => renderer.options.toggle_filter(Filter::Scanlines),
+                                KeyCode::F9 => renderer.options.toggle_filter(Filter::Crt),
+                                KeyCode::F10 => renderer.options.toggle_filter(Filter::Scale2x),
+                                KeyCode::F11 => window.set_fullscreen(match window.fullscreen() {
+                                    Some(_) => None,
+                                    None => Some(Fullscreen::Borderless(None))
+                                }),
+                                _ => ()
+                            }
+                            window.request_redraw();
+                        }
                         match code {
-                            KeyCode::Digit1 => keys[1] = state.is_pressed(),
-                            KeyCode::Digit2 => keys[2] = state.is_pressed(),
-                            KeyCode::Digit3 => keys[3] = state.is_pressed(),
-                            KeyCode::Digit4 => keys[0xC] = state.is_pressed(),
-                            KeyCode::KeyQ => keys[4] = state.is_pressed(),
-                            KeyCode::KeyW => keys[5] = state.is_pressed(),
-                            KeyCode::KeyE => keys[6] = state.is_pressed(),
-                            KeyCode::KeyR => keys[0xD] = state.is_pressed(),
-                            KeyCode::KeyA => keys[7] = state.is_pressed(),
-                            KeyCode::KeyS => keys[8] = state.is_pressed(),
-                            KeyCode::KeyD => keys[9] = state.is_pressed(),
-                            KeyCode::KeyF => keys[0xE] = state.is_pressed(),
-                            KeyCode::KeyZ => keys[0xA] = state.is_pressed(),
-                            KeyCode::KeyX => keys[0] = state.is_pressed(),
-                            KeyCode::KeyC => keys[0xB] = state.is_pressed(),
-                            KeyCode::KeyV => keys[0xF] = state.is_pressed(),
-                            _ => ()
+                            KeyCode::Backspace => {
+                                let _ = inputs.send(Input::Rewind(state.is_pressed()));
+                            },
+                            _ => if let Some(key) = keypad(code).filter(|_| !repeat) {
+                                let _ = inputs.send(Input::Key { key, pressed: state.is_pressed() });
+                            }
                         }
                     }
                 },
-                Event::WindowEvent { window_id, event: WindowEvent::CloseRequested } => {
+                Event::WindowEvent { event: WindowEvent::CloseRequested, .. } => {
+                    // let the emulator save the movie and close the WAV file
+                    let _ = inputs.send(Input::Quit);
+                    if let Some(thread) = thread.take() {
+                        let _ = thread.join();
+                    }
                     elwt.exit();
                 },
-                Event::AboutToWait => {
-                    window.request_redraw();
-                },
                 _ => ()
             }
         }).unwrap();
+    ExitCode::SUCCESS
+}
 
+fn title(state: &RunState) -> String {
+    match state {
+        RunState::Running => "CHIP-8".to_string(),
+        RunState::WaitingForKey(_) => "CHIP-8 - waiting for key".to_string(),
+        RunState::Halted(e) => format!("CHIP-8 - halted: {}", e),
+        RunState::Exited => "CHIP-8 - exited".to_string()
+    }
 }
 
-fn read_buffer<'a, D, W>(buffer: &mut softbuffer::Buffer<'a, D, W>, cpu: &Cpu)
-where D: winit::raw_window_handle::HasDisplayHandle, W: winit::raw_window_handle::HasWindowHandle {
-    let input = cpu.get_display_buffer();
-
-    for y in 0..SCREEN_HEIGHT {
-        for x in 0..SCREEN_WIDTH/8 {
-            for i in 0..8 {
-                let val = (input[y*SCREEN_WIDTH/8 + x] >> (7-i) & 0x01) as u32 * 255;
-                let slice = [val; SCALING - GAP_V];
-                let dx = (8 * x + i) * SCALING;
-                for sy in GAP_H..SCALING {
-                    let dy = y * SCALING + sy;
-                    let start = dy * W + dx;
-                    buffer[start + GAP_V..start + SCALING].copy_from_slice(&slice);
-                }
-            }
-        }
+/// A fresh seed per run from the std hasher keys, which come from the OS
+fn entropy_seed() -> u32 {
+    let hash = RandomState::new().build_hasher().finish();
+    (hash ^ hash >> 32) as u32
+}
+
+fn keypad(code: KeyCode) -> Option<u8> {
+    match code {
+        KeyCode::Digit1 => Some(1),
+        KeyCode::Digit2 => Some(2),
+        KeyCode::Digit3 => Some(3),
+        KeyCode::Digit4 => Some(0xC),
+        KeyCode::KeyQ => Some(4),
+        KeyCode::KeyW => Some(5),
+        KeyCode::KeyE => Some(6),
+        KeyCode::KeyR => Some(0xD),
+        KeyCode::KeyA => Some(7),
+        KeyCode::KeyS => Some(8),
+        KeyCode::KeyD => Some(9),
+        KeyCode::KeyF => Some(0xE),
+        KeyCode::KeyZ => Some(0xA),
+        KeyCode::KeyX => Some(0),
+        KeyCode::KeyC => Some(0xB),
+        KeyCode::KeyV => Some(0xF),
+        _ => None
+    }
+}
+
+fn save_slot(code: KeyCode) -> Option<u8> {
+    match code {
+        KeyCode::F1 => Some(1),
+        KeyCode::F2 => Some(2),
+        KeyCode::F3 => Some(3),
+        KeyCode::F4 => Some(4),
+        _ => None
     }
 }
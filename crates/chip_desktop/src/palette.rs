@@ -0,0 +1,30 @@
+//! Display colour schemes.
+use chip_core::globals::PLANE_COUNT;
+
+/// Colour of each pixel value, the bits of a value being the planes set in
+/// it: background, plane 1, plane 2, then both planes for XO-CHIP
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: [u32; 1 << PLANE_COUNT]
+}
+
+pub const PALETTES: [(&str, Palette); 4] = [
+    ("green", Palette { colors: [0x001400, 0x33ff66, 0x119944, 0xb0ffc0] }),
+    ("amber", Palette { colors: [0x1a0e00, 0xffb000, 0x995500, 0xffe0a0] }),
+    ("lcd", Palette { colors: [0x9bbc0f, 0x0f380f, 0x8bac0f, 0x306230] }),
+    ("high-contrast", Palette { colors: [0x000000, 0xffffff, 0xffff00, 0x00ffff] }),
+];
+
+impl Palette {
+    pub fn named(name: &str) -> Option<Palette> {
+        PALETTES.iter().find(|(n, _)| *n == name).map(|&(_, palette)| palette)
+    }
+    pub fn background(&self) -> u32 {
+        self.colors[0]
+    }
+}
+impl Default for Palette {
+    fn default() -> Self {
+        PALETTES[0].1
+    }
+}
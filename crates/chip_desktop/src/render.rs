@@ -0,0 +1,198 @@
+//! Drawing the display into the window.
+//!
+//! The planes are coloured through the palette, run through the filter
+//! chain and scaled. The display keeps its 2:1 aspect ratio in any window,
+//! centred with borders. The layout is computed on the hires grid so that
+//! switching resolution does not move the picture.
+use chip_core::globals::{HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH};
+
+use crate::{
+    emulator::FrameView,
+    filter::{self, Filter, Image},
+    palette::Palette
+};
+
+/// gap left between the pixels by the grid, as a fraction of a pixel
+const GAP: f32 = 0.25;
+
+pub struct RenderOptions {
+    pub palette: Palette,
+    /// scale by whole hires pixels only
+    pub integer: bool,
+    /// separate the pixels with thin lines of background, unless a
+    /// pixel-art filter has already reshaped them
+    pub grid: bool,
+    /// applied in order, pixel-art filters before scaling
+    pub filters: Vec<Filter>
+}
+impl RenderOptions {
+    /// Adds the filter, or removes it when already there
+    pub fn toggle_filter(&mut self, filter: Filter) {
+        match self.filters.iter().position(|&f| f == filter) {
+            Some(i) => { self.filters.remove(i); },
+            None => self.filters.push(filter)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+/// Largest centred area of the display aspect ratio fitting in the surface
+pub fn layout(width: usize, height: usize, integer: bool) -> Rect {
+    let scale = (width as f32 / HIRES_SCREEN_WIDTH as f32).min(height as f32 / HIRES_SCREEN_HEIGHT as f32);
+    // too small a window falls back to shrinking
+    let scale = if integer && scale >= 1. { scale.floor() } else { scale };
+    let (w, h) = ((HIRES_SCREEN_WIDTH as f32 * scale) as usize, (HIRES_SCREEN_HEIGHT as f32 * scale) as usize);
+    Rect { x: (width - w) / 2, y: (height - h) / 2, width: w, height: h }
+}
+
+/// Draws frames through the filter chain, keeping the buffers between frames
+pub struct Renderer {
+    pub options: RenderOptions,
+    image: Image,
+    upscaled: Image,
+    scratch: Vec<u32>
+}
+impl Renderer {
+    pub fn new(options: RenderOptions) -> Self {
+        Self { options, image: Image::default(), upscaled: Image::default(), scratch: Vec::new() }
+    }
+    /// Draws the display into a `width` x `height` surface
+    pub fn draw(&mut self, out: &mut [u32], width: usize, height: usize, view: &FrameView) {
+        let options = &self.options;
+        out.fill(options.palette.background());
+        let rect = layout(width, height, options.integer);
+        // nothing published yet
+        if view.width == 0 || rect.width == 0 { return }
+
+        colorize(view, &options.palette, &mut self.image);
+        let mut image = &self.image;
+        for filter in options.filters.iter().filter(|f| f.is_pixel_art()) {
+            match filter {
+                Filter::Scale2x => {
+                    filter::scale2x(image, &mut self.upscaled);
+                    std::mem::swap(&mut self.image, &mut self.upscaled);
+                    image = &self.image;
+                },
+                Filter::Scanlines | Filter::Crt => ()
+            }
+        }
+
+        let pixel = rect.width as f32 / image.width as f32;
+        let reshaped = image.width != view.width;
+        let gap = if options.grid && !reshaped { (pixel * GAP) as usize } else { 0 };
+        let columns = source_pixels(rect.width, image.width, gap);
+        let rows = source_pixels(rect.height, image.height, gap);
+        for (dy, sy) in rows.iter().enumerate() {
+            let Some(sy) = sy else { continue };
+            let start = (rect.y + dy) * width + rect.x;
+            let line = &image.pixels[sy * image.width..];
+            for (dst, sx) in out[start..start + rect.width].iter_mut().zip(&columns) {
+                if let Some(sx) = sx {
+                    *dst = line[*sx];
+                }
+            }
+        }
+
+        let area = &mut out[rect.y * width + rect.x..];
+        for filter in &options.filters {
+            match filter {
+                Filter::Scanlines => filter::scanlines(area, width, rect.width, rect.height),
+                Filter::Crt => filter::crt(area, width, rect.width, rect.height, &mut self.scratch),
+                Filter::Scale2x => ()
+            }
+        }
+    }
+}
+
+/// Looks up the palette colour of each pixel from its planes
+fn colorize(view: &FrameView, palette: &Palette, image: &mut Image) {
+    image.width = view.width;
+    image.height = view.height;
+    image.pixels.clear();
+    for i in 0..view.width * view.height {
+        let value = view.planes.iter().enumerate()
+            .map(|(p, plane)| plane.get(i / 8).map_or(0, |byte| (byte >> (7 - i % 8) & 1) << p))
+            .sum::<u8>();
+        image.pixels.push(palette.colors[value as usize]);
+    }
+}
+
+/// Source pixel under each of the `len` output pixels, None in the grid gaps
+fn source_pixels(len: usize, source_len: usize, gap: usize) -> Vec<Option<usize>> {
+    (0..len).map(|i| {
+        let src = i * source_len / len;
+        // first output pixel of that source pixel
+        let start = (src * len).div_ceil(source_len);
+        (i - start >= gap).then_some(src)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip_core::globals::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+    #[test]
+    fn letterbox() {
+        assert!(layout(1280, 640, false) == Rect { x: 0, y: 0, width: 1280, height: 640 });
+        // pillarboxed when too wide, letterboxed when too tall
+        assert!(layout(1000, 320, false) == Rect { x: 180, y: 0, width: 640, height: 320 });
+        assert!(layout(640, 500, false) == Rect { x: 0, y: 90, width: 640, height: 320 });
+        assert!(layout(700, 350, false).width == 700);
+        assert!(layout(700, 350, true) == Rect { x: 30, y: 15, width: 640, height: 320 });
+        assert!(layout(100, 50, true).width == 100);
+    }
+    #[test]
+    fn scales_pixels() {
+        let mut view = FrameView { width: SCREEN_WIDTH, height: SCREEN_HEIGHT, ..Default::default() };
+        view.planes[0] = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT / 8];
+        // top left pixel on
+        view.planes[0][0] = 0x80;
+        let palette = Palette { colors: [0, 1, 2, 3] };
+        let mut renderer = Renderer::new(RenderOptions { palette, integer: true, grid: false, filters: Vec::new() });
+        let (width, height) = (SCREEN_WIDTH * 8, SCREEN_HEIGHT * 8 + 20);
+        let mut out = vec![7; width * height];
+        renderer.draw(&mut out, width, height, &view);
+        let top = 10 * width;
+        assert!(out[..top].iter().all(|&p| p == 0));
+        assert!(out[top..top + 8] == [1; 8] && out[top + 8] == 0);
+        assert!(out[top + 7 * width + 7] == 1 && out[top + 8 * width] == 0);
+        // the grid takes a quarter of each pixel
+        renderer.options.grid = true;
+        renderer.draw(&mut out, width, height, &view);
+        assert!(out[top..top + 8] == [0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(out[top + 2 * width..top + 2 * width + 8] == [0, 0, 1, 1, 1, 1, 1, 1]);
+    }
+    #[test]
+    fn plane_colors() {
+        let mut view = FrameView { width: 16, height: 1, ..Default::default() };
+        view.planes = [vec![0b1010_0000, 0], vec![0b0110_0000, 0]];
+        let mut image = Image::default();
+        colorize(&view, &Palette { colors: [0, 1, 2, 3] }, &mut image);
+        assert!(image.pixels[..5] == [1, 2, 3, 0, 0]);
+    }
+    #[test]
+    fn filter_chain() {
+        let mut view = FrameView { width: SCREEN_WIDTH, height: SCREEN_HEIGHT, ..Default::default() };
+        view.planes[0] = vec![0xff; SCREEN_WIDTH * SCREEN_HEIGHT / 8];
+        let palette = Palette { colors: [0, 0xffffff, 0, 0] };
+        let mut renderer = Renderer::new(RenderOptions { palette, integer: true, grid: true, filters: Vec::new() });
+        renderer.options.toggle_filter(Filter::Scale2x);
+        renderer.options.toggle_filter(Filter::Scanlines);
+        let (width, height) = (HIRES_SCREEN_WIDTH * 2, HIRES_SCREEN_HEIGHT * 2);
+        let mut out = vec![0; width * height];
+        renderer.draw(&mut out, width, height, &view);
+        // no grid once upscaled, and every other line dimmed
+        assert!(out[..width].iter().all(|&p| p == 0xffffff));
+        assert!(out[width..2 * width].iter().all(|&p| p < 0xffffff && p > 0));
+        renderer.options.toggle_filter(Filter::Scanlines);
+        assert!(renderer.options.filters == [Filter::Scale2x]);
+    }
+}
@@ -0,0 +1,11 @@
+use std::{fs, path::Path};
+
+/// Reads a binary ROM, `.8o` sources are assembled first
+pub fn load(path: &Path) -> Result<Vec<u8>, String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if path.extension().is_none_or(|ext| ext != "8o") {
+        return Ok(data);
+    }
+    let src = String::from_utf8(data).map_err(|_| format!("{}: not valid UTF-8", path.display()))?;
+    chip_asm::assemble(&src).map_err(|e| format!("{}:{}", path.display(), e))
+}
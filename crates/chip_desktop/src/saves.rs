@@ -0,0 +1,23 @@
+use std::{fs, io, path::PathBuf};
+
+use chip_core::Cpu;
+
+const SAVE_DIR: &str = "saves";
+
+fn slot_path(slot: u8) -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(format!("slot{}.state", slot))
+}
+
+pub fn save(cpu: &Cpu, slot: u8) -> io::Result<()> {
+    let mut data = vec![0; cpu.snapshot_size()];
+    let size = cpu.snapshot(&mut data)
+        .map_err(|e| io::Error::other(format!("{:?}", e)))?;
+    fs::create_dir_all(SAVE_DIR)?;
+    fs::write(slot_path(slot), &data[..size])
+}
+
+pub fn load(cpu: &mut Cpu, slot: u8) -> io::Result<()> {
+    let data = fs::read(slot_path(slot))?;
+    cpu.restore(&data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))
+}
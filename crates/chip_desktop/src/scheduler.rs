@@ -0,0 +1,63 @@
+use std::time::{Duration, Instant};
+
+/// Frames run back to back after a stall, any further delay is dropped
+const MAX_CATCH_UP: u32 = 4;
+
+/// Fixed timestep clock: accumulates the elapsed wall time and hands it out
+/// in whole frames, so the emulation speed does not depend on the redraws.
+pub struct Scheduler {
+    frame: Duration,
+    last: Instant,
+    accumulator: Duration
+}
+impl Scheduler {
+    pub fn new(frame: Duration, now: Instant) -> Self {
+        Self { frame, last: now, accumulator: Duration::ZERO }
+    }
+    /// Number of frames to run at `now`
+    pub fn due(&mut self, now: Instant) -> u32 {
+        self.accumulator += now.saturating_duration_since(self.last);
+        self.last = now;
+        let frames = (self.accumulator.as_nanos() / self.frame.as_nanos()) as u32;
+        if frames > MAX_CATCH_UP {
+            self.accumulator = Duration::ZERO;
+            return MAX_CATCH_UP;
+        }
+        self.accumulator -= self.frame * frames;
+        frames
+    }
+    /// When the next frame is due
+    pub fn deadline(&self) -> Instant {
+        self.last + (self.frame - self.accumulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn accumulates() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(FRAME, start);
+        assert!(scheduler.due(start + Duration::from_millis(10)) == 0);
+        assert!(scheduler.deadline() == start + FRAME);
+        assert!(scheduler.due(start + Duration::from_millis(20)) == 1);
+        // the 4 ms left over count towards the next frame
+        assert!(scheduler.due(start + Duration::from_millis(44)) == 1);
+        assert!(scheduler.deadline() == start + FRAME * 3);
+    }
+    #[test]
+    fn catch_up_and_drop() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new(FRAME, start);
+        assert!(scheduler.due(start + FRAME * 3) == 3);
+        let now = start + FRAME * 3 + Duration::from_secs(2);
+        assert!(scheduler.due(now) == MAX_CATCH_UP);
+        // back on schedule from there
+        assert!(scheduler.deadline() == now + FRAME);
+        assert!(scheduler.due(now + FRAME) == 1);
+    }
+}
@@ -0,0 +1,68 @@
+use std::{fs, io, path::PathBuf};
+
+use chip_core::{movie::Movie, Cpu};
+
+/// Input source of the running machine
+pub enum Session {
+    Live,
+    Recording { movie: Movie, path: PathBuf },
+    Playing { movie: Movie, frame: usize }
+}
+impl Session {
+    pub fn record(movie: Movie, path: PathBuf) -> Self {
+        println!("Recording to {}", path.display());
+        Session::Recording { movie, path }
+    }
+    /// Loads a movie and returns the machine in its initial state
+    pub fn play(path: PathBuf, rom: &[u8]) -> io::Result<(Self, Cpu)> {
+        let data = fs::read(&path)?;
+        let movie = Movie::from_bytes(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e)))?;
+        if !movie.matches_rom(rom) {
+            eprintln!("Warning: {} was recorded with a different ROM", path.display());
+        }
+        println!("Playing {} ({} frames)", path.display(), movie.len());
+        let cpu = movie.start(rom)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        Ok((Session::Playing { movie, frame: 0 }, cpu))
+    }
+    /// Frame length of the active movie, 0 being VIP timing
+    pub fn steps_per_frame(&self) -> Option<u16> {
+        match self {
+            Session::Live => None,
+            Session::Recording { movie, .. } | Session::Playing { movie, .. } => Some(movie.steps_per_frame)
+        }
+    }
+    pub fn is_live(&self) -> bool {
+        matches!(self, Session::Live)
+    }
+    /// Returns the key state for the next frame.
+    /// Recordings log the live keys, playback replaces them.
+    pub fn frame_keys(&mut self, live: [bool; 0x10]) -> [bool; 0x10] {
+        match self {
+            Session::Live => live,
+            Session::Recording { movie, .. } => {
+                movie.record_frame(live);
+                live
+            },
+            Session::Playing { movie, frame } => {
+                if let Some(keys) = movie.frame_keys(*frame) {
+                    *frame += 1;
+                    return keys;
+                }
+                println!("Playback finished after {} frames", frame);
+                *self = Session::Live;
+                live
+            }
+        }
+    }
+    /// Writes out a pending recording
+    pub fn finish(&mut self) -> io::Result<()> {
+        if let Session::Recording { movie, path } = self {
+            fs::write(&path, movie.to_bytes())?;
+            println!("Saved {} frames to {}", movie.len(), path.display());
+        }
+        *self = Session::Live;
+        Ok(())
+    }
+}
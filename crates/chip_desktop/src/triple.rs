@@ -0,0 +1,102 @@
+//! Lock-free triple buffer.
+//!
+//! The writer fills the back slot and publishes it by swapping it with the
+//! middle one, the reader picks up the middle slot when it is fresh. Each side
+//! owns the index of its slot, so neither ever waits for the other and the
+//! reader always sees the latest complete value.
+use std::{
+    cell::UnsafeCell,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc
+    }
+};
+
+/// Set on the middle index when it holds a value the reader has not taken
+const FRESH: u8 = 0b100;
+
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    middle: AtomicU8
+}
+// SAFETY: a slot is only accessed through the index currently owned by one
+// side, and the indices change hands through the atomic middle.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+pub struct Writer<T> {
+    shared: Arc<Shared<T>>,
+    back: u8
+}
+pub struct Reader<T> {
+    shared: Arc<Shared<T>>,
+    front: u8
+}
+
+pub fn triple_buffer<T: Clone>(init: T) -> (Writer<T>, Reader<T>) {
+    let shared = Arc::new(Shared {
+        slots: [UnsafeCell::new(init.clone()), UnsafeCell::new(init.clone()), UnsafeCell::new(init)],
+        middle: AtomicU8::new(1)
+    });
+    (Writer { shared: shared.clone(), back: 0 }, Reader { shared, front: 2 })
+}
+
+impl<T> Writer<T> {
+    /// The slot to fill, holding an older value
+    pub fn back(&mut self) -> &mut T {
+        // SAFETY: the back slot belongs to the writer until published
+        unsafe { &mut *self.shared.slots[self.back as usize].get() }
+    }
+    /// Hands the back slot over to the reader
+    pub fn publish(&mut self) {
+        let old = self.shared.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = old & !FRESH;
+    }
+}
+impl<T> Reader<T> {
+    /// The latest published value, and whether it is new since the last read
+    pub fn read(&mut self) -> (&T, bool) {
+        let fresh = self.shared.middle.load(Ordering::Relaxed) & FRESH != 0;
+        if fresh {
+            let old = self.shared.middle.swap(self.front, Ordering::AcqRel);
+            self.front = old & !FRESH;
+        }
+        // SAFETY: the front slot belongs to the reader until it swaps it back
+        (unsafe { &*self.shared.slots[self.front as usize].get() }, fresh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert!(reader.read() == (&0, false));
+        *writer.back() = 1;
+        writer.publish();
+        *writer.back() = 2;
+        writer.publish();
+        assert!(reader.read() == (&2, true));
+        assert!(reader.read() == (&2, false));
+    }
+    #[test]
+    fn across_threads() {
+        let (mut writer, mut reader) = triple_buffer([0u32; 64]);
+        let thread = std::thread::spawn(move || {
+            for i in 1..=10_000 {
+                writer.back().fill(i);
+                writer.publish();
+            }
+        });
+        let mut last = 0;
+        while last < 10_000 {
+            let (val, _) = reader.read();
+            // never torn, never older than what was read before
+            assert!(val.iter().all(|&v| v == val[0]));
+            assert!(val[0] >= last);
+            last = val[0];
+        }
+        thread.join().unwrap();
+    }
+}